@@ -0,0 +1,141 @@
+//! A data source whose backend is picked at runtime from the URL scheme.
+use std::fmt;
+
+use crate::config::DataSourceConfig;
+use crate::error::ConfigError;
+use crate::health::HealthStatus;
+use crate::named::Backend;
+use crate::stats::PoolStats;
+use crate::DataSource;
+#[cfg(feature = "with-mysql")]
+use crate::MySqlDataSource;
+#[cfg(feature = "with-postgres")]
+use crate::PgDataSource;
+#[cfg(feature = "with-sqlite")]
+use crate::SqliteDataSource;
+
+/// 任意数据源
+///
+/// Has a variant for each SQL backend whose feature is enabled.
+#[derive(Clone, Debug)]
+pub enum AnyDataSource {
+    #[cfg(feature = "with-mysql")]
+    MySql(MySqlDataSource),
+    #[cfg(feature = "with-postgres")]
+    Pg(PgDataSource),
+    #[cfg(feature = "with-sqlite")]
+    Sqlite(SqliteDataSource),
+}
+
+impl AnyDataSource {
+    /// Builds the data source matching the scheme of the configured URL.
+    pub async fn from_config(cfg: DataSourceConfig) -> Result<Self, ConfigError> {
+        let scheme = url::Url::parse(cfg.get_url())
+            .map(|url| url.scheme().to_string())
+            .map_err(|e| ConfigError::MalformedUrl {
+                key: cfg.url_key(),
+                reason: e.to_string(),
+            })?;
+        match Backend::from_scheme(&scheme) {
+            #[cfg(feature = "with-mysql")]
+            Some(Backend::MySql) => Ok(AnyDataSource::MySql(
+                MySqlDataSource::from_config(cfg).await?,
+            )),
+            #[cfg(feature = "with-postgres")]
+            Some(Backend::Pg) => Ok(AnyDataSource::Pg(PgDataSource::from_config(cfg).await?)),
+            #[cfg(feature = "with-sqlite")]
+            Some(Backend::Sqlite) => Ok(AnyDataSource::Sqlite(
+                SqliteDataSource::from_config(cfg).await?,
+            )),
+            _ => Err(ConfigError::MalformedUrl {
+                key: cfg.url_key(),
+                reason: format!("no SQL backend is available for {}://", scheme),
+            }),
+        }
+    }
+
+    pub fn backend(&self) -> Backend {
+        match self {
+            #[cfg(feature = "with-mysql")]
+            AnyDataSource::MySql(_) => Backend::MySql,
+            #[cfg(feature = "with-postgres")]
+            AnyDataSource::Pg(_) => Backend::Pg,
+            #[cfg(feature = "with-sqlite")]
+            AnyDataSource::Sqlite(_) => Backend::Sqlite,
+        }
+    }
+
+    pub fn get_url(&self) -> String {
+        match self {
+            #[cfg(feature = "with-mysql")]
+            AnyDataSource::MySql(data_source) => data_source.get_url(),
+            #[cfg(feature = "with-postgres")]
+            AnyDataSource::Pg(data_source) => data_source.get_url(),
+            #[cfg(feature = "with-sqlite")]
+            AnyDataSource::Sqlite(data_source) => data_source.get_url(),
+        }
+    }
+
+    pub fn redacted_url(&self) -> String {
+        match self {
+            #[cfg(feature = "with-mysql")]
+            AnyDataSource::MySql(data_source) => data_source.redacted_url(),
+            #[cfg(feature = "with-postgres")]
+            AnyDataSource::Pg(data_source) => data_source.redacted_url(),
+            #[cfg(feature = "with-sqlite")]
+            AnyDataSource::Sqlite(data_source) => data_source.redacted_url(),
+        }
+    }
+
+    /// Runs the health check of the backend.
+    pub async fn health_check(&self) -> HealthStatus {
+        match self {
+            #[cfg(feature = "with-mysql")]
+            AnyDataSource::MySql(data_source) => data_source.health_check().await,
+            #[cfg(feature = "with-postgres")]
+            AnyDataSource::Pg(data_source) => data_source.health_check().await,
+            #[cfg(feature = "with-sqlite")]
+            AnyDataSource::Sqlite(data_source) => data_source.health_check().await,
+        }
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        match self {
+            #[cfg(feature = "with-mysql")]
+            AnyDataSource::MySql(data_source) => data_source.pool_stats(),
+            #[cfg(feature = "with-postgres")]
+            AnyDataSource::Pg(data_source) => data_source.pool_stats(),
+            #[cfg(feature = "with-sqlite")]
+            AnyDataSource::Sqlite(data_source) => data_source.pool_stats(),
+        }
+    }
+}
+
+impl fmt::Display for AnyDataSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.redacted_url())
+    }
+}
+
+/// Reads `DATABASE_URL` and `DATABASE_MAX_POOL_SIZE`/`DATABASE_MIN_POOL_SIZE`.
+pub async fn any_data_source() -> AnyDataSource {
+    try_any_data_source()
+        .await
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub async fn try_any_data_source() -> Result<AnyDataSource, ConfigError> {
+    AnyDataSource::from_config(DataSourceConfig::from_env("DATABASE")?).await
+}
+
+/// Reads `{NAME}_DATABASE_URL` and the matching pool sizes.
+pub async fn any_data_source_named(name: &str) -> AnyDataSource {
+    try_any_data_source_named(name)
+        .await
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub async fn try_any_data_source_named(name: &str) -> Result<AnyDataSource, ConfigError> {
+    let prefix = format!("{}_DATABASE", name.to_uppercase());
+    AnyDataSource::from_config(DataSourceConfig::from_env(&prefix)?).await
+}
@@ -0,0 +1,165 @@
+//! Non-blocking redis data source for use on the tokio runtime.
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+
+use redis::aio::MultiplexedConnection;
+use redis::{FromRedisValue, RedisResult};
+use tokio::sync::Mutex;
+
+use crate::env;
+use crate::error::ConfigError;
+use crate::health::HealthStatus;
+use crate::named::Backend;
+use crate::redact::redact_url;
+use crate::redis_pool::server_version;
+use crate::stats::PoolStats;
+
+/// 异步 Redis 数据源
+///
+/// Commands are pipelined over one multiplexed connection that every clone
+/// shares, so unlike `RedisDataSource` there is no pool to size and nothing
+/// blocks the runtime.
+#[derive(Clone)]
+pub struct AsyncRedisDataSource {
+    pub url: String,
+    client: redis::Client,
+    connection: Arc<Mutex<Option<MultiplexedConnection>>>,
+}
+
+impl AsyncRedisDataSource {
+    /// Connects to `url`.
+    pub async fn from_url(url: &str) -> Result<Self, ConfigError> {
+        AsyncRedisDataSource::connect("url", url).await
+    }
+
+    async fn connect(key: &str, url: &str) -> Result<Self, ConfigError> {
+        let client = redis::Client::open(url).map_err(|e| ConfigError::MalformedUrl {
+            key: key.to_string(),
+            reason: e.to_string(),
+        })?;
+        let data_source = AsyncRedisDataSource {
+            url: url.to_string(),
+            client,
+            connection: Arc::new(Mutex::new(None)),
+        };
+        data_source
+            .get_connection()
+            .await
+            .map_err(|e| ConfigError::connection(key, e))?;
+        Ok(data_source)
+    }
+
+    /// Returns a handle on the shared connection, connecting first if needed.
+    ///
+    /// A connection that failed stays shared until `reset`; `query` takes
+    /// care of that.
+    pub async fn get_connection(&self) -> RedisResult<MultiplexedConnection> {
+        let mut connection = self.connection.lock().await;
+        if let Some(conn) = connection.as_ref() {
+            return Ok(conn.clone());
+        }
+        let conn = self.client.get_multiplexed_tokio_connection().await?;
+        *connection = Some(conn.clone());
+        Ok(conn)
+    }
+
+    /// Drops the shared connection, e.g. after an I/O error, so the next
+    /// `get_connection` reconnects.
+    pub async fn reset(&self) {
+        self.connection.lock().await.take();
+    }
+
+    /// Runs `cmd` on the shared connection. An I/O error drops the
+    /// connection, so the next command reconnects, e.g. after a restart.
+    pub async fn query<T: FromRedisValue>(&self, cmd: &redis::Cmd) -> RedisResult<T> {
+        let mut conn = self.get_connection().await?;
+        let result = cmd.query_async(&mut conn).await;
+        if let Err(e) = &result {
+            if e.is_io_error() || e.is_connection_dropped() {
+                self.reset().await;
+            }
+        }
+        result
+    }
+
+    /// Like `RedisDataSource::health_check`, over the shared connection.
+    pub async fn health_check(&self) -> HealthStatus {
+        let started = Instant::now();
+        let ping = self.query::<String>(&redis::cmd("PING")).await;
+        let latency = started.elapsed();
+        match ping {
+            Ok(_) => {
+                let version = self
+                    .query::<String>(redis::cmd("INFO").arg("server"))
+                    .await
+                    .ok()
+                    .and_then(|info| server_version(&info));
+                HealthStatus::up(latency, version, self.pool_stats().await)
+            }
+            Err(e) => HealthStatus::down(latency, self.pool_stats().await, e),
+        }
+    }
+
+    // The shared connection counts as a pool of one.
+    async fn pool_stats(&self) -> PoolStats {
+        let size = self.connection.lock().await.is_some() as u32;
+        PoolStats {
+            size,
+            idle: size,
+            max_size: 1,
+            ..PoolStats::default()
+        }
+    }
+
+    pub fn get_url(&self) -> String {
+        self.url.to_string()
+    }
+
+    pub fn redacted_url(&self) -> String {
+        redact_url(&self.url)
+    }
+}
+
+impl fmt::Debug for AsyncRedisDataSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncRedisDataSource")
+            .field("url", &self.redacted_url())
+            .finish()
+    }
+}
+
+impl fmt::Display for AsyncRedisDataSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.redacted_url())
+    }
+}
+
+pub async fn async_redis_data_source() -> AsyncRedisDataSource {
+    try_async_redis_data_source()
+        .await
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub async fn try_async_redis_data_source() -> Result<AsyncRedisDataSource, ConfigError> {
+    connect_env(Backend::Redis.env_prefix()).await
+}
+
+/// Reads `{NAME}_REDIS_URL`.
+pub async fn async_redis_data_source_named(name: &str) -> AsyncRedisDataSource {
+    try_async_redis_data_source_named(name)
+        .await
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub async fn try_async_redis_data_source_named(
+    name: &str,
+) -> Result<AsyncRedisDataSource, ConfigError> {
+    connect_env(&Backend::Redis.named_env_prefix(name)).await
+}
+
+async fn connect_env(prefix: &str) -> Result<AsyncRedisDataSource, ConfigError> {
+    env::load()?;
+    let url = env::backend_url(Backend::Redis, prefix)?;
+    AsyncRedisDataSource::connect(&format!("{}_URL", prefix), &url).await
+}
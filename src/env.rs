@@ -0,0 +1,373 @@
+//! Helpers for reading settings from the process environment.
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+
+use crate::error::ConfigError;
+use crate::file::{FileConfig, CONFIG_FILES};
+use crate::named::Backend;
+use crate::params::ConnectParams;
+
+/// Name of the active profile, from `TDF_PROFILE` in the environment or in `.env`.
+// dotenv deprecates its iterators in favour of `from_path`, which sets the
+// variables itself and so cannot apply the files in our order.
+#[allow(deprecated)]
+pub fn profile() -> Option<String> {
+    std::env::var("TDF_PROFILE")
+        .ok()
+        .or_else(|| {
+            dotenv::dotenv_iter()
+                .ok()?
+                .filter_map(Result::ok)
+                .find(|(key, _)| key == "TDF_PROFILE")
+                .map(|(_, value)| value)
+        })
+        .filter(|profile| !profile.is_empty())
+}
+
+lazy_static! {
+    // Values `load` took from files, so `reload` can tell them apart from
+    // the real environment.
+    static ref FROM_FILES: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
+}
+
+/// Loads `.env`, the config file and their profile overlays into the process environment.
+///
+/// Nothing overrides a variable that is already set, so files are applied
+/// from most to least specific, which gives defaults < `tdf.toml` < `.env` <
+/// `tdf.{profile}.toml` < `.env.{profile}` < real environment.
+pub(crate) fn load() -> Result<(), ConfigError> {
+    let vars = file_vars()?;
+    let mut from_files = FROM_FILES.lock().unwrap_or_else(|e| e.into_inner());
+    for (key, value) in vars {
+        if std::env::var_os(&key).is_none() {
+            std::env::set_var(&key, &value);
+            from_files.insert(key, value);
+        }
+    }
+    Ok(())
+}
+
+/// Reads the files again and updates the variables taken from them,
+/// returning the keys whose value changed, was added or was removed.
+///
+/// Variables of the real environment still win over the files.
+pub(crate) fn reload() -> Result<Vec<String>, ConfigError> {
+    let vars = file_vars()?.into_iter().collect();
+    let mut from_files = FROM_FILES.lock().unwrap_or_else(|e| e.into_inner());
+    Ok(update(vars, &mut from_files))
+}
+
+/// Applies `vars`, read from the files, to the environment, where
+/// `from_files` holds the variables the files set before.
+pub(crate) fn update(
+    vars: HashMap<String, String>,
+    from_files: &mut HashMap<String, String>,
+) -> Vec<String> {
+    let mut changed = Vec::new();
+    from_files.retain(|key, _| {
+        let kept = vars.contains_key(key);
+        if !kept {
+            std::env::remove_var(key);
+            changed.push(key.clone());
+        }
+        kept
+    });
+    for (key, value) in vars {
+        let stale = match from_files.get(&key) {
+            Some(old) => *old != value,
+            None => std::env::var_os(&key).is_none(),
+        };
+        if stale {
+            std::env::set_var(&key, &value);
+            changed.push(key.clone());
+            from_files.insert(key, value);
+        }
+    }
+    changed.sort();
+    changed
+}
+
+/// The variables of every file.
+fn file_vars() -> Result<Vars, ConfigError> {
+    let profile = profile();
+    let profile_dotenv = match &profile {
+        Some(profile) => dotenv_vars(&format!(".env.{}", profile)),
+        None => Vec::new(),
+    };
+    let profile_file = match profile.as_deref().and_then(FileConfig::find_profile) {
+        Some(path) => FileConfig::from_path(&path)?.to_env(),
+        None => Vec::new(),
+    };
+    let file = match FileConfig::find() {
+        Some(path) => FileConfig::from_path(&path)?.to_env(),
+        None => Vec::new(),
+    };
+    Ok(overlay(
+        profile_dotenv,
+        profile_file,
+        dotenv_vars(".env"),
+        file,
+    ))
+}
+
+type Vars = Vec<(String, String)>;
+
+/// Merges the variables of `.env.{profile}`, `tdf.{profile}.toml`, `.env`
+/// and `tdf.toml`, keeping for each key the value of the most specific
+/// file, so both profile files override the base `.env`.
+pub(crate) fn overlay(profile_dotenv: Vars, profile_file: Vars, dotenv: Vars, file: Vars) -> Vars {
+    let mut seen = HashSet::new();
+    profile_dotenv
+        .into_iter()
+        .chain(profile_file)
+        .chain(dotenv)
+        .chain(file)
+        .filter(|(key, _)| seen.insert(key.clone()))
+        .collect()
+}
+
+/// The variables of a dotenv file.
+///
+/// dotenv replaces `${VAR}` while parsing, with an empty string when `VAR`
+/// is not set yet, so `${VAR_FILE}` secrets would be lost. Values with a
+/// `${` reference are therefore kept as written, and `var` resolves them.
+#[allow(deprecated)]
+pub(crate) fn dotenv_vars(file: &str) -> Vec<(String, String)> {
+    let raw = raw_values(file);
+    dotenv::from_filename_iter(file)
+        .map(|iter| {
+            iter.filter_map(Result::ok)
+                .map(|(key, value)| match raw.get(&key) {
+                    Some(raw) => (key, raw.clone()),
+                    None => (key, value),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Values of `file` with a `${` reference, as written. Single quoted values,
+/// which dotenv keeps literally, and values with escapes are left to dotenv.
+fn raw_values(file: &str) -> HashMap<String, String> {
+    // Looked up like dotenv does, in the current directory or above.
+    let text = std::env::current_dir()
+        .ok()
+        .and_then(|dir| {
+            dir.ancestors()
+                .map(|dir| dir.join(file))
+                .find(|path| path.is_file())
+        })
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .unwrap_or_default();
+    let mut values = HashMap::new();
+    for line in text.lines().map(str::trim) {
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) if !key.trim_start().starts_with('#') => (key.trim(), value.trim()),
+            _ => continue,
+        };
+        let value = match value.strip_prefix('"') {
+            Some(quoted) => match quoted.find('"') {
+                Some(end) => &quoted[..end],
+                None => continue,
+            },
+            None => value.split(" #").next().unwrap_or_default().trim_end(),
+        };
+        if value.contains("${") && !value.contains(&['\\', '\''][..]) {
+            values
+                .entry(key.to_string())
+                .or_insert_with(|| value.to_string());
+        }
+    }
+    values
+}
+
+/// Every file `load` may read, whether it exists or not.
+pub(crate) fn watched_files() -> Vec<PathBuf> {
+    let mut files = vec![PathBuf::from(".env")];
+    let profile = profile();
+    if let Some(profile) = &profile {
+        files.push(PathBuf::from(format!(".env.{}", profile)));
+    }
+    match std::env::var_os("TDF_CONFIG") {
+        Some(path) => files.push(PathBuf::from(path)),
+        None => files.extend(CONFIG_FILES.iter().map(PathBuf::from)),
+    }
+    files.extend(profile.as_deref().and_then(FileConfig::find_profile));
+    files
+}
+
+/// How deep `${VAR}` references may nest before they are taken for a cycle.
+const MAX_DEPTH: usize = 8;
+
+/// Characters escaped in the user and password of a URL.
+const USERINFO: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b':')
+    .add(b';')
+    .add(b'<')
+    .add(b'=')
+    .add(b'>')
+    .add(b'?')
+    .add(b'@')
+    .add(b'[')
+    .add(b'\\')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// Whether `key`, or `{key}_FILE`, is set.
+pub(crate) fn is_set(key: &str) -> bool {
+    std::env::var_os(key).is_some() || std::env::var_os(format!("{}_FILE", key)).is_some()
+}
+
+/// Reads a required variable.
+///
+/// When `key` is unset the value is read from the file named by `{key}_FILE`,
+/// as with Docker and Kubernetes secrets, and used verbatim. Otherwise
+/// `${VAR}` references in the value are replaced by the value of `VAR`,
+/// which is itself resolved the same way. A value replacing a reference in
+/// the user or password of a URL is percent-encoded, so a password may
+/// contain `@`, `/` or `%` as is.
+pub(crate) fn var(key: &str) -> Result<String, ConfigError> {
+    resolve(key, 0)
+}
+
+/// Reads an optional variable, resolved like `var`.
+pub(crate) fn opt_var(key: &str) -> Result<Option<String>, ConfigError> {
+    if is_set(key) {
+        var(key).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+fn resolve(key: &str, depth: usize) -> Result<String, ConfigError> {
+    if depth > MAX_DEPTH {
+        return Err(ConfigError::InvalidValue {
+            key: key.to_string(),
+            reason: "${...} references nest too deeply, is there a cycle?".to_string(),
+        });
+    }
+    if let Ok(value) = std::env::var(key) {
+        return interpolate(key, &value, depth);
+    }
+    match std::env::var(format!("{}_FILE", key)) {
+        Ok(path) => read_secret(&path),
+        Err(_) => Err(ConfigError::MissingVar {
+            key: key.to_string(),
+        }),
+    }
+}
+
+fn read_secret(path: &str) -> Result<String, ConfigError> {
+    std::fs::read_to_string(path)
+        .map(|secret| secret.trim_end_matches(&['\r', '\n'][..]).to_string())
+        .map_err(|e| ConfigError::InvalidFile {
+            path: path.to_string(),
+            reason: e.to_string(),
+        })
+}
+
+fn interpolate(key: &str, value: &str, depth: usize) -> Result<String, ConfigError> {
+    let mut resolved = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        resolved.push_str(&rest[..start]);
+        let reference = &rest[start + 2..];
+        let end = reference
+            .find('}')
+            .ok_or_else(|| ConfigError::InvalidValue {
+                key: key.to_string(),
+                reason: "unterminated ${".to_string(),
+            })?;
+        let value = resolve(&reference[..end], depth + 1)?;
+        rest = &reference[end + 1..];
+        if in_userinfo(&resolved, rest) {
+            resolved.extend(utf8_percent_encode(&value, USERINFO));
+        } else {
+            resolved.push_str(&value);
+        }
+    }
+    resolved.push_str(rest);
+    Ok(resolved)
+}
+
+/// Whether a reference between `before`, already resolved, and `after`, not
+/// yet resolved, lies in the user or password of a URL.
+fn in_userinfo(before: &str, after: &str) -> bool {
+    let authority = match before.find("://") {
+        Some(start) => &before[start + 3..],
+        None => return false,
+    };
+    !authority.contains(&['/', '@'][..])
+        && after
+            .find(&['/', '@'][..])
+            .is_some_and(|end| after[end..].starts_with('@'))
+}
+
+/// Reads a required variable and checks that it parses as a URL.
+pub(crate) fn url(key: &str) -> Result<String, ConfigError> {
+    let value = var(key)?;
+    url::Url::parse(&value).map_err(|e| ConfigError::MalformedUrl {
+        key: key.to_string(),
+        reason: e.to_string(),
+    })?;
+    Ok(value)
+}
+
+/// Reads `{prefix}_URL`, or assembles it from `{prefix}_HOST` and friends when unset.
+pub(crate) fn backend_url(backend: Backend, prefix: &str) -> Result<String, ConfigError> {
+    let key = format!("{}_URL", prefix);
+    if !is_set(&key) {
+        if let Some(params) = ConnectParams::from_env(backend, prefix)? {
+            return params.to_url().map_err(|e| ConfigError::MalformedUrl {
+                key: format!("{}_HOST", prefix),
+                reason: e.to_string(),
+            });
+        }
+    }
+    url(&key)
+}
+
+/// Reads an optional pool size, falling back to `default` when unset.
+pub(crate) fn pool_size(key: &str, default: u32) -> Result<u32, ConfigError> {
+    match opt_var(key)? {
+        None => Ok(default),
+        Some(value) => value
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| ConfigError::InvalidPoolSize {
+                key: key.to_string(),
+                value,
+            }),
+    }
+}
+
+/// Reads an optional value of any parsable type, falling back to `default` when unset.
+pub(crate) fn parse<T: FromStr>(key: &str, default: T) -> Result<T, ConfigError>
+where
+    T::Err: std::fmt::Display,
+{
+    match opt_var(key)? {
+        None => Ok(default),
+        Some(value) => value
+            .trim()
+            .parse::<T>()
+            .map_err(|e| ConfigError::InvalidValue {
+                key: key.to_string(),
+                reason: format!("{:?}: {}", value, e),
+            }),
+    }
+}
@@ -0,0 +1,104 @@
+//! Errors raised while reading configuration and building data sources.
+use std::error::Error;
+use std::fmt;
+
+/// 配置错误
+///
+/// Every variant names the environment key, or the file, it was raised for.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// A required variable is not set.
+    MissingVar { key: String },
+    /// The variable is set but does not hold a valid URL.
+    MalformedUrl { key: String, reason: String },
+    /// The variable does not hold a usable pool size.
+    InvalidPoolSize { key: String, value: String },
+    /// The variable is set but its value cannot be used.
+    InvalidValue { key: String, reason: String },
+    /// The variable is set more than once in the same file.
+    DuplicateKey { key: String, path: String },
+    /// A configuration file could not be read or parsed.
+    InvalidFile { path: String, reason: String },
+    /// The backend configured by the variable could not be reached.
+    Connection {
+        key: String,
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// Every connection attempt failed, `source` is the last error.
+    RetriesExhausted {
+        key: String,
+        attempts: u32,
+        source: Box<dyn Error + Send + Sync>,
+    },
+}
+
+impl ConfigError {
+    pub(crate) fn connection<E>(key: &str, source: E) -> ConfigError
+    where
+        E: Into<Box<dyn Error + Send + Sync>>,
+    {
+        ConfigError::Connection {
+            key: key.to_string(),
+            source: source.into(),
+        }
+    }
+
+    /// The environment key this error was raised for, or the file path.
+    pub fn key(&self) -> &str {
+        match self {
+            ConfigError::MissingVar { key }
+            | ConfigError::MalformedUrl { key, .. }
+            | ConfigError::InvalidPoolSize { key, .. }
+            | ConfigError::InvalidValue { key, .. }
+            | ConfigError::DuplicateKey { key, .. }
+            | ConfigError::Connection { key, .. }
+            | ConfigError::RetriesExhausted { key, .. } => key,
+            ConfigError::InvalidFile { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::MissingVar { key } => write!(f, "{} must be set", key),
+            ConfigError::MalformedUrl { key, reason } => {
+                write!(f, "{} is not a valid URL: {}", key, reason)
+            }
+            ConfigError::InvalidPoolSize { key, value } => {
+                write!(f, "{} is not a valid pool size: {:?}", key, value)
+            }
+            ConfigError::InvalidValue { key, reason } => {
+                write!(f, "{} is not valid: {}", key, reason)
+            }
+            ConfigError::DuplicateKey { key, path } => {
+                write!(f, "{} is set more than once in {}", key, path)
+            }
+            ConfigError::InvalidFile { path, reason } => {
+                write!(f, "{} could not be loaded: {}", path, reason)
+            }
+            ConfigError::Connection { key, source } => {
+                write!(f, "failed to connect using {}: {}", key, source)
+            }
+            ConfigError::RetriesExhausted {
+                key,
+                attempts,
+                source,
+            } => write!(
+                f,
+                "failed to connect using {} after {} attempts: {}",
+                key, attempts, source
+            ),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Connection { source, .. }
+            | ConfigError::RetriesExhausted { source, .. } => Some(&**source),
+            _ => None,
+        }
+    }
+}
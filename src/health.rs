@@ -0,0 +1,41 @@
+//! Health reports for readiness probes.
+use std::fmt;
+use std::time::Duration;
+
+use crate::stats::PoolStats;
+
+/// 健康状态
+///
+/// A failed check is reported through `healthy` and `error` rather than as
+/// an `Err`, so a probe can always render the latency and pool state.
+#[derive(Debug, Clone)]
+pub struct HealthStatus {
+    pub healthy: bool,
+    /// Round trip of the check query, or of the failed attempt.
+    pub latency: Duration,
+    pub server_version: Option<String>,
+    pub pool: PoolStats,
+    pub error: Option<String>,
+}
+
+impl HealthStatus {
+    pub(crate) fn up(latency: Duration, server_version: Option<String>, pool: PoolStats) -> Self {
+        HealthStatus {
+            healthy: true,
+            latency,
+            server_version,
+            pool,
+            error: None,
+        }
+    }
+
+    pub(crate) fn down(latency: Duration, pool: PoolStats, error: impl fmt::Display) -> Self {
+        HealthStatus {
+            healthy: false,
+            latency,
+            server_version: None,
+            pool,
+            error: Some(error.to_string()),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Prometheus export of pool statistics, compiled with the `metrics` feature.
+use std::fmt::Write;
+
+use crate::stats::PoolStats;
+
+/// Renders pool statistics in the Prometheus text exposition format, with
+/// each pool's name as the `pool` label.
+///
+/// ```
+/// use tdf_config::{prometheus_text, PoolStats};
+///
+/// let text = prometheus_text(&[("orders", PoolStats::default())]);
+/// assert!(text.contains("tdf_pool_connections{pool=\"orders\"} 0"));
+/// ```
+pub fn prometheus_text(pools: &[(&str, PoolStats)]) -> String {
+    let series: [(&str, &str, &str, fn(&PoolStats) -> String); 8] = [
+        (
+            "tdf_pool_connections",
+            "gauge",
+            "Open connections, idle or in use.",
+            |stats| stats.size.to_string(),
+        ),
+        (
+            "tdf_pool_idle_connections",
+            "gauge",
+            "Open connections not in use.",
+            |stats| stats.idle.to_string(),
+        ),
+        (
+            "tdf_pool_max_connections",
+            "gauge",
+            "Maximum number of connections.",
+            |stats| stats.max_size.to_string(),
+        ),
+        (
+            "tdf_pool_in_use_connections",
+            "gauge",
+            "Connections handed out, however they were taken.",
+            |stats| stats.in_use().to_string(),
+        ),
+        (
+            "tdf_pool_exhausted",
+            "gauge",
+            "1 when every connection is in use, else 0.",
+            |stats| (stats.is_exhausted() as u8).to_string(),
+        ),
+        (
+            "tdf_pool_acquired_total",
+            "counter",
+            "Connections handed out.",
+            |stats| stats.acquired.to_string(),
+        ),
+        (
+            "tdf_pool_acquire_wait_seconds_total",
+            "counter",
+            "Time spent waiting for a connection.",
+            |stats| stats.wait_time.as_secs_f64().to_string(),
+        ),
+        (
+            "tdf_pool_acquire_failures_total",
+            "counter",
+            "Attempts to get a connection that failed.",
+            |stats| stats.acquire_failures.to_string(),
+        ),
+    ];
+    let mut text = String::new();
+    for (name, kind, help, value) in series.iter() {
+        writeln!(text, "# HELP {} {}", name, help).ok();
+        writeln!(text, "# TYPE {} {}", name, kind).ok();
+        for (pool, stats) in pools {
+            writeln!(
+                text,
+                "{}{{pool=\"{}\"}} {}",
+                name,
+                escape(pool),
+                value(stats)
+            )
+            .ok();
+        }
+    }
+    text
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,81 @@
+//! MySQL data source, compiled with the `with-mysql` feature.
+use futures::future::BoxFuture;
+use sqlx::prelude::*;
+use sqlx::{MySqlConnection, MySqlPool};
+
+use crate::config::DataSourceConfig;
+use crate::error::ConfigError;
+use crate::named::Backend;
+use crate::params::ConnectParams;
+use crate::registry::REGISTRY;
+use crate::sql::{SqlConnection, SqlDataSource};
+use crate::DataSource;
+
+/// See `SqlDataSource`.
+pub type MySqlDataSource = SqlDataSource<MySqlConnection>;
+
+impl SqlConnection for MySqlConnection {
+    const DATA_SOURCE: &'static str = "MySqlDataSource";
+
+    fn ping(pool: &MySqlPool) -> BoxFuture<'_, sqlx::Result<()>> {
+        Box::pin(async move { sqlx::query("SELECT 1").execute(pool).await.map(|_| ()) })
+    }
+
+    fn server_version(pool: &MySqlPool) -> BoxFuture<'_, Option<String>> {
+        Box::pin(async move {
+            let mut cursor = sqlx::query(r#"SELECT version() v"#).fetch(pool);
+            let row = cursor.next().await.ok()??;
+            row.try_get::<&str, &str>("v").ok().map(str::to_string)
+        })
+    }
+}
+
+impl MySqlDataSource {
+    /// The decoded components of the URL.
+    pub fn params(&self) -> ConnectParams {
+        ConnectParams::from_url(&self.get_url()).unwrap_or_default()
+    }
+}
+
+/// Builds a new pool on every call, see `mysql()` for a shared one.
+pub async fn mysql_data_source() -> MySqlDataSource {
+    try_mysql_data_source()
+        .await
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub async fn try_mysql_data_source() -> Result<MySqlDataSource, ConfigError> {
+    MySqlDataSource::from_config(DataSourceConfig::from_env("MYSQL")?).await
+}
+
+/// Reads `{NAME}_MYSQL_URL` and the matching pool sizes.
+pub async fn mysql_data_source_named(name: &str) -> MySqlDataSource {
+    try_mysql_data_source_named(name)
+        .await
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub async fn try_mysql_data_source_named(name: &str) -> Result<MySqlDataSource, ConfigError> {
+    let prefix = Backend::MySql.named_env_prefix(name);
+    MySqlDataSource::from_config(DataSourceConfig::from_env(&prefix)?).await
+}
+
+/// The MySQL data source held by `REGISTRY`, its pool is built on first use.
+pub async fn mysql() -> MySqlDataSource {
+    try_mysql().await.unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub async fn try_mysql() -> Result<MySqlDataSource, ConfigError> {
+    REGISTRY.mysql().await
+}
+
+/// The shared data source named `name`, its pool is built on first use.
+pub async fn mysql_named(name: &str) -> MySqlDataSource {
+    try_mysql_named(name)
+        .await
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub async fn try_mysql_named(name: &str) -> Result<MySqlDataSource, ConfigError> {
+    REGISTRY.mysql_named(name).await
+}
@@ -0,0 +1,84 @@
+//! Named data sources, configured through prefixed env keys.
+//!
+//! A data source named `ORDERS` reads `ORDERS_MYSQL_URL`,
+//! `ORDERS_MYSQL_MAX_POOL_SIZE` and so on, next to the unnamed `MYSQL_URL`.
+use crate::env;
+
+/// 后端类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    MySql,
+    Pg,
+    Redis,
+    Sqlite,
+}
+
+impl Backend {
+    /// Prefix of the unnamed data source's keys, e.g. `MYSQL`.
+    pub fn env_prefix(self) -> &'static str {
+        match self {
+            Backend::MySql => "MYSQL",
+            Backend::Pg => "PG",
+            Backend::Redis => "REDIS",
+            Backend::Sqlite => "SQLITE",
+        }
+    }
+
+    /// URL schemes accepted for this backend, the usual one first.
+    pub fn schemes(self) -> &'static [&'static str] {
+        match self {
+            Backend::MySql => &["mysql"],
+            Backend::Pg => &["postgres", "postgresql"],
+            Backend::Redis => &["redis", "rediss", "redis+unix", "unix"],
+            Backend::Sqlite => &["sqlite"],
+        }
+    }
+
+    /// Prefix of a named data source's keys, e.g. `ORDERS_MYSQL`.
+    pub fn named_env_prefix(self, name: &str) -> String {
+        format!("{}_{}", name.to_uppercase(), self.env_prefix())
+    }
+
+    /// The backend accepting URLs with `scheme`.
+    pub fn from_scheme(scheme: &str) -> Option<Backend> {
+        [Backend::MySql, Backend::Pg, Backend::Redis, Backend::Sqlite]
+            .iter()
+            .copied()
+            .find(|backend| backend.schemes().contains(&scheme))
+    }
+
+    /// The backend whose keys start with `prefix`, named or not.
+    pub(crate) fn from_env_prefix(prefix: &str) -> Option<Backend> {
+        [Backend::MySql, Backend::Pg, Backend::Redis, Backend::Sqlite]
+            .iter()
+            .copied()
+            .find(|backend| {
+                let own = backend.env_prefix();
+                prefix == own || prefix.ends_with(&format!("_{}", own))
+            })
+    }
+}
+
+/// Lists the names that have a `{NAME}_{BACKEND}_URL`, its `_FILE` variant
+/// or a `{NAME}_{BACKEND}_HOST` set, sorted.
+///
+/// The unnamed data source is not listed.
+pub fn configured_names(backend: Backend) -> Vec<String> {
+    env::load().ok();
+    let prefix = format!("_{}_", backend.env_prefix());
+    let mut names: Vec<String> = std::env::vars()
+        .filter_map(|(key, _)| {
+            let key = key.strip_suffix("_FILE").unwrap_or(&key);
+            key.strip_suffix("URL")
+                .or_else(|| key.strip_suffix("HOST"))
+                .or_else(|| key.strip_suffix("SENTINELS"))
+                .or_else(|| key.strip_suffix("CLUSTER_NODES"))
+                .and_then(|rest| rest.strip_suffix(prefix.as_str()))
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
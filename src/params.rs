@@ -0,0 +1,133 @@
+//! Connection parameters given as discrete keys instead of a single URL.
+use std::fmt;
+
+use percent_encoding::percent_decode_str;
+
+use crate::env;
+use crate::error::ConfigError;
+use crate::named::Backend;
+use crate::redact::MASK;
+use crate::tls;
+
+/// 连接参数
+///
+/// Values are kept decoded, `to_url` takes care of percent-encoding, so a
+/// password may contain `@`, `/` or `:` as is.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct ConnectParams {
+    pub scheme: String,
+    pub host: String,
+    pub port: Option<u16>,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub database: Option<String>,
+    pub ssl_mode: Option<String>,
+}
+
+impl ConnectParams {
+    /// Splits a URL into its decoded components.
+    pub fn from_url(url: &str) -> Result<Self, url::ParseError> {
+        let parsed = url::Url::parse(url)?;
+        let decode = |s: &str| percent_decode_str(s).decode_utf8_lossy().into_owned();
+        Ok(ConnectParams {
+            scheme: parsed.scheme().to_string(),
+            host: parsed.host_str().unwrap_or_default().to_string(),
+            port: parsed.port(),
+            user: Some(parsed.username())
+                .filter(|user| !user.is_empty())
+                .map(decode),
+            password: parsed.password().map(decode),
+            database: parsed
+                .path_segments()
+                .and_then(|mut segments| segments.next())
+                .filter(|database| !database.is_empty())
+                .map(decode),
+            ssl_mode: parsed
+                .query_pairs()
+                .find(|(key, _)| key == "sslmode" || key == "ssl-mode")
+                .map(|(_, value)| value.into_owned()),
+        })
+    }
+
+    /// Assembles the URL, percent-encoding every component.
+    pub fn to_url(&self) -> Result<String, url::ParseError> {
+        let host = if self.host.contains(':') && !self.host.starts_with('[') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        let mut url = url::Url::parse(&format!("{}://{}", self.scheme, host))?;
+        // The setters only fail for URLs without a host, which `url` is not.
+        if let Some(user) = &self.user {
+            url.set_username(user).ok();
+        }
+        if let Some(password) = &self.password {
+            url.set_password(Some(password)).ok();
+        }
+        url.set_port(self.port).ok();
+        if let Some(database) = &self.database {
+            if let Ok(mut segments) = url.path_segments_mut() {
+                segments.clear().push(database);
+            }
+        }
+        if let Some(ssl_mode) = &self.ssl_mode {
+            let key = if self.scheme == "mysql" {
+                "ssl-mode"
+            } else {
+                "sslmode"
+            };
+            url.query_pairs_mut().append_pair(key, ssl_mode);
+        }
+        Ok(url.to_string())
+    }
+
+    /// Reads `{prefix}_HOST`, `_PORT`, `_USER`, `_PASSWORD`, `_DATABASE` and
+    /// `{prefix}_SSL_MODE`; `None` when `{prefix}_HOST` is unset.
+    pub(crate) fn from_env(backend: Backend, prefix: &str) -> Result<Option<Self>, ConfigError> {
+        let key = |suffix: &str| format!("{}_{}", prefix, suffix);
+        let host = match env::opt_var(&key("HOST"))? {
+            Some(host) => host,
+            None => return Ok(None),
+        };
+        let port = match env::opt_var(&key("PORT"))? {
+            Some(port) => {
+                Some(
+                    port.trim()
+                        .parse::<u16>()
+                        .map_err(|_| ConfigError::InvalidValue {
+                            key: key("PORT"),
+                            reason: format!("{:?} is not a port number", port),
+                        })?,
+                )
+            }
+            None => None,
+        };
+        let ssl_mode = match backend {
+            Backend::Pg | Backend::MySql => tls::ssl_mode_from_env(prefix)?,
+            Backend::Redis | Backend::Sqlite => None,
+        };
+        Ok(Some(ConnectParams {
+            scheme: backend.schemes()[0].to_string(),
+            host,
+            port,
+            user: env::opt_var(&key("USER"))?,
+            password: env::opt_var(&key("PASSWORD"))?,
+            database: env::opt_var(&key("DATABASE"))?,
+            ssl_mode,
+        }))
+    }
+}
+
+impl fmt::Debug for ConnectParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectParams")
+            .field("scheme", &self.scheme)
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("user", &self.user)
+            .field("password", &self.password.as_ref().map(|_| MASK))
+            .field("database", &self.database)
+            .field("ssl_mode", &self.ssl_mode)
+            .finish()
+    }
+}
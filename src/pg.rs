@@ -0,0 +1,79 @@
+//! PostgreSQL data source, compiled with the `with-postgres` feature.
+use futures::future::BoxFuture;
+use sqlx::prelude::*;
+use sqlx::{PgConnection, PgPool};
+
+use crate::config::DataSourceConfig;
+use crate::error::ConfigError;
+use crate::named::Backend;
+use crate::params::ConnectParams;
+use crate::registry::REGISTRY;
+use crate::sql::{SqlConnection, SqlDataSource};
+use crate::DataSource;
+
+/// See `SqlDataSource`.
+pub type PgDataSource = SqlDataSource<PgConnection>;
+
+impl SqlConnection for PgConnection {
+    const DATA_SOURCE: &'static str = "PgDataSource";
+
+    fn ping(pool: &PgPool) -> BoxFuture<'_, sqlx::Result<()>> {
+        Box::pin(async move { sqlx::query("SELECT 1").execute(pool).await.map(|_| ()) })
+    }
+
+    fn server_version(pool: &PgPool) -> BoxFuture<'_, Option<String>> {
+        Box::pin(async move {
+            let mut cursor = sqlx::query(r#"SELECT version() v"#).fetch(pool);
+            let row = cursor.next().await.ok()??;
+            row.try_get::<&str, &str>("v").ok().map(str::to_string)
+        })
+    }
+}
+
+impl PgDataSource {
+    /// The decoded components of the URL.
+    pub fn params(&self) -> ConnectParams {
+        ConnectParams::from_url(&self.get_url()).unwrap_or_default()
+    }
+}
+
+/// Builds a new pool on every call, see `pg()` for a shared one.
+pub async fn pg_data_source() -> PgDataSource {
+    try_pg_data_source()
+        .await
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub async fn try_pg_data_source() -> Result<PgDataSource, ConfigError> {
+    PgDataSource::from_config(DataSourceConfig::from_env("PG")?).await
+}
+
+/// Reads `{NAME}_PG_URL` and the matching pool sizes.
+pub async fn pg_data_source_named(name: &str) -> PgDataSource {
+    try_pg_data_source_named(name)
+        .await
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub async fn try_pg_data_source_named(name: &str) -> Result<PgDataSource, ConfigError> {
+    let prefix = Backend::Pg.named_env_prefix(name);
+    PgDataSource::from_config(DataSourceConfig::from_env(&prefix)?).await
+}
+
+/// The PostgreSQL data source held by `REGISTRY`, its pool is built on first use.
+pub async fn pg() -> PgDataSource {
+    try_pg().await.unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub async fn try_pg() -> Result<PgDataSource, ConfigError> {
+    REGISTRY.pg().await
+}
+
+/// The shared data source named `name`, its pool is built on first use.
+pub async fn pg_named(name: &str) -> PgDataSource {
+    try_pg_named(name).await.unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub async fn try_pg_named(name: &str) -> Result<PgDataSource, ConfigError> {
+    REGISTRY.pg_named(name).await
+}
@@ -0,0 +1,20 @@
+//! Masking of credentials before URLs reach logs.
+
+/// Replacement for a password, or for a whole URL that cannot be parsed.
+pub const MASK: &str = "****";
+
+/// Returns `url` with its password replaced by `****`.
+///
+/// A URL that does not parse is masked completely, as there is no telling
+/// where the secret is.
+pub fn redact_url(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(mut parsed) => {
+            if parsed.password().is_some() {
+                parsed.set_password(Some(MASK)).ok();
+            }
+            parsed.to_string()
+        }
+        Err(_) => MASK.to_string(),
+    }
+}
@@ -0,0 +1,238 @@
+//! Pooled Redis Cluster data source, compiled with the `with-redis` feature.
+use std::fmt;
+use std::sync::Arc;
+
+use r2d2::{ManageConnection, PooledConnection};
+use redis::cluster::{ClusterClient, ClusterConnection};
+use redis::RedisError;
+
+use crate::env;
+use crate::error::ConfigError;
+use crate::health::HealthStatus;
+use crate::named::Backend;
+use crate::redact::redact_url;
+use crate::redis_config::RedisPoolConfig;
+use crate::redis_pool::{check_health, pool_stats, MetricsHandler};
+use crate::reload::affected;
+use crate::stats::{AcquireMetrics, PoolStats};
+use crate::swap::{Slots, Swap};
+
+/// r2d2 manager of cluster connections, which follow `MOVED` and `ASK`
+/// redirections and route each command to the node owning its slot.
+pub struct RedisClusterConnectionManager {
+    client: ClusterClient,
+}
+
+impl RedisClusterConnectionManager {
+    pub fn new(nodes: &[String]) -> Result<Self, RedisError> {
+        let nodes: Vec<&str> = nodes.iter().map(String::as_str).collect();
+        Ok(RedisClusterConnectionManager {
+            client: ClusterClient::open(nodes)?,
+        })
+    }
+}
+
+impl ManageConnection for RedisClusterConnectionManager {
+    type Connection = ClusterConnection;
+    type Error = RedisError;
+
+    fn connect(&self) -> Result<ClusterConnection, RedisError> {
+        self.client.get_connection()
+    }
+
+    fn is_valid(&self, conn: &mut ClusterConnection) -> Result<(), RedisError> {
+        redis::cmd("PING").query(conn)
+    }
+
+    // A broken node is reconnected by the cluster connection itself.
+    fn has_broken(&self, _conn: &mut ClusterConnection) -> bool {
+        false
+    }
+}
+
+impl fmt::Debug for RedisClusterConnectionManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisClusterConnectionManager").finish()
+    }
+}
+
+/// 集群数据源
+///
+/// Shared and reloaded like `RedisDataSource`.
+#[derive(Clone)]
+pub struct RedisClusterDataSource {
+    current: Swap<Cluster>,
+    metrics: Arc<AcquireMetrics>,
+}
+
+#[derive(Clone)]
+struct Cluster {
+    nodes: Vec<String>,
+    pool: r2d2::Pool<RedisClusterConnectionManager>,
+}
+
+impl RedisClusterDataSource {
+    pub fn nodes(&self) -> Vec<String> {
+        self.current.get().nodes
+    }
+
+    pub fn get_pool(&self) -> r2d2::Pool<RedisClusterConnectionManager> {
+        self.current.get().pool
+    }
+
+    pub fn get(&self) -> Result<PooledConnection<RedisClusterConnectionManager>, r2d2::Error> {
+        self.get_pool().get()
+    }
+
+    /// The node URLs with their passwords masked, safe to log.
+    pub fn redacted_nodes(&self) -> Vec<String> {
+        self.nodes().iter().map(|node| redact_url(node)).collect()
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        pool_stats(&self.get_pool(), &self.metrics)
+    }
+
+    /// Like `RedisDataSource::health_check`, on whichever node answers.
+    pub async fn health_check(&self) -> HealthStatus {
+        check_health(self.get_pool(), || self.pool_stats()).await
+    }
+}
+
+impl fmt::Debug for RedisClusterDataSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.get_pool().state();
+        f.debug_struct("RedisClusterDataSource")
+            .field("nodes", &self.redacted_nodes())
+            .field("connections", &state.connections)
+            .field("idle_connections", &state.idle_connections)
+            .finish()
+    }
+}
+
+impl fmt::Display for RedisClusterDataSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.redacted_nodes().join(","))
+    }
+}
+
+lazy_static! {
+    // Same as REDIS_POOLS, for clusters.
+    static ref REDIS_CLUSTERS: Slots<RedisClusterDataSource> = Slots::new();
+
+    // Follows reloads like `REDIS_POOL`.
+    pub static ref REDIS_CLUSTER_POOL: RedisClusterDataSource =
+        try_redis_cluster_data_source().unwrap_or_else(|e| panic!("{}", e));
+}
+
+pub fn get_redis_cluster_connection() -> PooledConnection<RedisClusterConnectionManager> {
+    REDIS_CLUSTER_POOL.get().unwrap()
+}
+
+/// Reads `REDIS_CLUSTER_NODES`, built on first success and shared like `REDIS_POOL`.
+pub fn redis_cluster_data_source() -> RedisClusterDataSource {
+    try_redis_cluster_data_source().unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn try_redis_cluster_data_source() -> Result<RedisClusterDataSource, ConfigError> {
+    shared_cluster(&cluster_prefix(Backend::Redis.env_prefix()))
+}
+
+/// Reads `{NAME}_REDIS_CLUSTER_NODES`; the pool is shared by every caller using `name`.
+pub fn redis_cluster_data_source_named(name: &str) -> RedisClusterDataSource {
+    try_redis_cluster_data_source_named(name).unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn try_redis_cluster_data_source_named(
+    name: &str,
+) -> Result<RedisClusterDataSource, ConfigError> {
+    shared_cluster(&cluster_prefix(&Backend::Redis.named_env_prefix(name)))
+}
+
+pub(crate) fn cluster_prefix(prefix: &str) -> String {
+    format!("{}_CLUSTER", prefix)
+}
+
+fn shared_cluster(prefix: &str) -> Result<RedisClusterDataSource, ConfigError> {
+    REDIS_CLUSTERS.get_or_try_init(prefix, || {
+        let metrics = Arc::new(AcquireMetrics::default());
+        Ok(RedisClusterDataSource {
+            current: Swap::new(build_cluster(prefix, metrics.clone())?),
+            metrics,
+        })
+    })
+}
+
+/// Same as `redis_pool::reload`, for clusters.
+pub(crate) fn reload(keys: &[String]) -> (Vec<String>, Vec<ConfigError>) {
+    let stale: Vec<_> = REDIS_CLUSTERS
+        .built()
+        .into_iter()
+        .filter(|(prefix, _)| affected(prefix, keys))
+        .collect();
+    let mut rebuilt = Vec::new();
+    let mut errors = Vec::new();
+    for (prefix, source) in stale {
+        match build_cluster(&prefix, source.metrics.clone()) {
+            Ok(cluster) => {
+                source.current.replace(cluster);
+                rebuilt.push(prefix);
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    (rebuilt, errors)
+}
+
+/// Reads `{prefix}_NODES`, `{prefix}_PASSWORD` and the pool settings read
+/// by `RedisPoolConfig`.
+fn build_cluster(prefix: &str, metrics: Arc<AcquireMetrics>) -> Result<Cluster, ConfigError> {
+    env::load()?;
+    let key = format!("{}_NODES", prefix);
+    let nodes = nodes(prefix)?;
+    let manager =
+        RedisClusterConnectionManager::new(&nodes).map_err(|e| ConfigError::MalformedUrl {
+            key: key.clone(),
+            reason: e.to_string(),
+        })?;
+    let cfg = RedisPoolConfig::from_env(prefix)?;
+    let pool = cfg
+        .builder()
+        .event_handler(Box::new(MetricsHandler(metrics)))
+        .build(manager)
+        .map_err(|e| ConfigError::connection(&key, e))?;
+    Ok(Cluster { nodes, pool })
+}
+
+/// The comma separated `{prefix}_NODES`, `host:port` or URLs, with
+/// `{prefix}_PASSWORD` applied to those without one.
+pub(crate) fn nodes(prefix: &str) -> Result<Vec<String>, ConfigError> {
+    let key = format!("{}_NODES", prefix);
+    let password = env::opt_var(&format!("{}_PASSWORD", prefix))?;
+    let mut nodes = Vec::new();
+    for node in env::var(&key)?.split(',').map(str::trim) {
+        if node.is_empty() {
+            continue;
+        }
+        let node = if node.contains("://") {
+            node.to_string()
+        } else {
+            format!("redis://{}", node)
+        };
+        let mut url = url::Url::parse(&node).map_err(|e| ConfigError::MalformedUrl {
+            key: key.clone(),
+            reason: e.to_string(),
+        })?;
+        if let (Some(password), None) = (&password, url.password()) {
+            url.set_password(Some(password)).ok();
+        }
+        nodes.push(url.to_string());
+    }
+    if nodes.is_empty() {
+        return Err(ConfigError::InvalidValue {
+            key,
+            reason: "expected host:port,host:port".to_string(),
+        });
+    }
+    Ok(nodes)
+}
@@ -0,0 +1,87 @@
+//! Redis pool settings, compiled with the `with-redis` feature.
+use std::time::Duration;
+
+use crate::config::CONNECT_TIMEOUT;
+use crate::env;
+use crate::error::ConfigError;
+use crate::REDIS_POOL_SIZE;
+
+/// Default time a connection may stay idle, same as r2d2.
+const REDIS_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Settings of one redis pool, read from `{prefix}_*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RedisPoolConfig {
+    pub(crate) max_size: u32,
+    /// `None` keeps `max_size` connections open, as r2d2 does.
+    pub(crate) min_idle: Option<u32>,
+    /// How long to wait for a connection, including connecting.
+    pub(crate) connect_timeout: Duration,
+    pub(crate) idle_timeout: Option<Duration>,
+}
+
+impl RedisPoolConfig {
+    /// Reads `{prefix}_MAX_POOL_SIZE`, `{prefix}_MIN_IDLE`,
+    /// `{prefix}_CONNECT_TIMEOUT_MS` and `{prefix}_IDLE_TIMEOUT_SECS`, where 0
+    /// disables it.
+    ///
+    /// The redis driver has no TLS support, so `{prefix}_TLS_CA_CERT`,
+    /// `{prefix}_TLS_CERT` and `{prefix}_TLS_KEY` are refused rather than ignored.
+    pub(crate) fn from_env(prefix: &str) -> Result<Self, ConfigError> {
+        let key = |suffix: &str| format!("{}_{}", prefix, suffix);
+        let max_size = env::pool_size(&key("MAX_POOL_SIZE"), REDIS_POOL_SIZE)?;
+        if max_size == 0 {
+            return Err(ConfigError::InvalidPoolSize {
+                key: key("MAX_POOL_SIZE"),
+                value: max_size.to_string(),
+            });
+        }
+        let min_idle = match env::opt_var(&key("MIN_IDLE"))? {
+            Some(_) => Some(env::pool_size(&key("MIN_IDLE"), 0)?),
+            None => None,
+        };
+        if let Some(min_idle) = min_idle.filter(|&min_idle| min_idle > max_size) {
+            return Err(ConfigError::InvalidPoolSize {
+                key: key("MIN_IDLE"),
+                value: min_idle.to_string(),
+            });
+        }
+        let connect_timeout = Duration::from_millis(env::parse(
+            &key("CONNECT_TIMEOUT_MS"),
+            CONNECT_TIMEOUT.as_millis() as u64,
+        )?);
+        if connect_timeout == Duration::default() {
+            return Err(ConfigError::InvalidValue {
+                key: key("CONNECT_TIMEOUT_MS"),
+                reason: "must be positive".to_string(),
+            });
+        }
+        let idle_timeout = Some(Duration::from_secs(env::parse(
+            &key("IDLE_TIMEOUT_SECS"),
+            REDIS_IDLE_TIMEOUT.as_secs(),
+        )?))
+        .filter(|&timeout| timeout > Duration::default());
+        for suffix in &["TLS_CA_CERT", "TLS_CERT", "TLS_KEY"] {
+            if env::is_set(&key(suffix)) {
+                return Err(ConfigError::InvalidValue {
+                    key: key(suffix),
+                    reason: "TLS is not supported by the redis driver".to_string(),
+                });
+            }
+        }
+        Ok(RedisPoolConfig {
+            max_size,
+            min_idle,
+            connect_timeout,
+            idle_timeout,
+        })
+    }
+
+    pub(crate) fn builder<M: r2d2::ManageConnection>(&self) -> r2d2::Builder<M> {
+        r2d2::Pool::builder()
+            .max_size(self.max_size)
+            .min_idle(self.min_idle)
+            .connection_timeout(self.connect_timeout)
+            .idle_timeout(self.idle_timeout)
+    }
+}
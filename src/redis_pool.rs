@@ -0,0 +1,319 @@
+//! Pooled, blocking redis data source, compiled with the `with-redis` feature.
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use r2d2::event::{CheckoutEvent, TimeoutEvent};
+use r2d2::{HandleEvent, ManageConnection, PooledConnection};
+use redis::{ConnectionLike, RedisError};
+
+use crate::env;
+use crate::error::ConfigError;
+use crate::health::HealthStatus;
+use crate::named::Backend;
+use crate::redact::redact_url;
+use crate::redis_config::RedisPoolConfig;
+use crate::reload::affected;
+use crate::sentinel::{self, Sentinel};
+use crate::stats::{AcquireMetrics, PoolStats};
+use crate::swap::{Pooled, Slots, Swap};
+
+/// r2d2 manager of redis connections, to `{prefix}_URL` or to the master
+/// Sentinel reports when each connection is made.
+pub struct RedisManager {
+    target: Target,
+}
+
+enum Target {
+    Url(redis::Client),
+    /// Connections are checked with `ROLE` on checkout, so those left on a
+    /// master demoted by a failover are dropped.
+    Sentinel(Sentinel),
+}
+
+impl ManageConnection for RedisManager {
+    type Connection = redis::Connection;
+    type Error = RedisError;
+
+    fn connect(&self) -> Result<redis::Connection, RedisError> {
+        match &self.target {
+            Target::Url(client) => client.get_connection(),
+            Target::Sentinel(sentinel) => sentinel.connect(),
+        }
+    }
+
+    fn is_valid(&self, conn: &mut redis::Connection) -> Result<(), RedisError> {
+        match &self.target {
+            Target::Url(_) => redis::cmd("PING").query(conn),
+            Target::Sentinel(_) => sentinel::check_master(conn),
+        }
+    }
+
+    fn has_broken(&self, conn: &mut redis::Connection) -> bool {
+        !conn.is_open()
+    }
+}
+
+// The client's Debug output includes the password.
+impl fmt::Debug for RedisManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisManager").finish()
+    }
+}
+
+/// Redis 数据源
+///
+/// Every clone takes its connections from the same pool, and `reload`
+/// swaps it for all of them.
+#[derive(Clone)]
+pub struct RedisDataSource {
+    current: Swap<Pooled<r2d2::Pool<RedisManager>>>,
+    metrics: Arc<AcquireMetrics>,
+}
+
+impl RedisDataSource {
+    /// With Sentinel, the URL of the master when the pool was built.
+    pub fn get_url(&self) -> String {
+        self.current.get().url
+    }
+    pub fn get_pool(self) -> r2d2::Pool<RedisManager> {
+        self.pool()
+    }
+
+    /// The pool in use now, a reload swaps in another.
+    pub fn pool(&self) -> r2d2::Pool<RedisManager> {
+        self.current.get().pool
+    }
+
+    /// Takes a connection from the current pool.
+    pub fn get(&self) -> Result<PooledConnection<RedisManager>, r2d2::Error> {
+        self.pool().get()
+    }
+
+    pub fn redacted_url(&self) -> String {
+        redact_url(&self.get_url())
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        pool_stats(&self.pool(), &self.metrics)
+    }
+
+    /// Sends `PING`, then reads the server version from `INFO server`.
+    pub async fn health_check(&self) -> HealthStatus {
+        check_health(self.pool(), || self.pool_stats()).await
+    }
+}
+
+/// The health check of the redis data sources. The pool is blocking, so it
+/// runs on tokio's blocking threads.
+pub(crate) async fn check_health<M>(
+    pool: r2d2::Pool<M>,
+    stats: impl Fn() -> PoolStats,
+) -> HealthStatus
+where
+    M: ManageConnection,
+    M::Connection: ConnectionLike,
+{
+    let checked = tokio::task::spawn_blocking(move || {
+        let started = Instant::now();
+        let ping = pool.get().map_err(|e| e.to_string()).and_then(|mut conn| {
+            redis::cmd("PING")
+                .query::<String>(&mut *conn)
+                .map(|_| conn)
+                .map_err(|e| e.to_string())
+        });
+        let latency = started.elapsed();
+        let version = ping.map(|mut conn| {
+            redis::cmd("INFO")
+                .arg("server")
+                .query::<String>(&mut *conn)
+                .ok()
+                .and_then(|info| server_version(&info))
+        });
+        (latency, version)
+    })
+    .await;
+    match checked {
+        Ok((latency, Ok(version))) => HealthStatus::up(latency, version, stats()),
+        Ok((latency, Err(e))) => HealthStatus::down(latency, stats(), e),
+        Err(e) => HealthStatus::down(Duration::default(), stats(), e),
+    }
+}
+
+pub(crate) fn pool_stats<M: ManageConnection>(
+    pool: &r2d2::Pool<M>,
+    metrics: &AcquireMetrics,
+) -> PoolStats {
+    let state = pool.state();
+    metrics.stats(state.connections, state.idle_connections, pool.max_size())
+}
+
+/// Feeds r2d2's checkout and timeout events into the pool's counters.
+#[derive(Debug)]
+pub(crate) struct MetricsHandler(pub(crate) Arc<AcquireMetrics>);
+
+impl HandleEvent for MetricsHandler {
+    fn handle_checkout(&self, event: CheckoutEvent) {
+        self.0.record(event.duration(), true);
+    }
+
+    fn handle_timeout(&self, event: TimeoutEvent) {
+        self.0.record(event.timeout(), false);
+    }
+}
+
+pub(crate) fn server_version(info: &str) -> Option<String> {
+    info.lines()
+        .find_map(|line| line.strip_prefix("redis_version:"))
+        .map(|version| version.trim().to_string())
+}
+
+// The manager's Debug output includes the password, so only the pool state is shown.
+impl fmt::Debug for RedisDataSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisDataSource")
+            .field("url", &self.redacted_url())
+            .field("state", &self.pool().state())
+            .finish()
+    }
+}
+
+impl fmt::Display for RedisDataSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.redacted_url())
+    }
+}
+
+lazy_static! {
+    // Holds each shared data source, keyed by env prefix, once it has been
+    // built successfully, so a failed attempt can be retried by the next caller.
+    static ref REDIS_POOLS: Slots<RedisDataSource> = Slots::new();
+
+    // Follows reloads, `REDIS_POOL.get()` takes a connection from the current pool.
+    pub static ref REDIS_POOL: RedisDataSource =
+        try_redis_data_source().unwrap_or_else(|e| panic!("{}", e));
+
+    // Used to update core data into redis master, such as person, role and dept etc.
+    pub static ref MASTER_REDIS_POOL: RedisDataSource =
+        try_redis_data_source_named(MASTER).unwrap_or_else(|e| panic!("{}", e));
+}
+
+/// Name of the master redis data source, read from `MASTER_REDIS_URL`.
+pub(crate) const MASTER: &str = "master";
+
+/// Returns the current shared redis pool, building it on first success.
+pub fn try_redis_pool() -> Result<r2d2::Pool<RedisManager>, ConfigError> {
+    shared_redis_pool(Backend::Redis.env_prefix()).map(|source| source.pool())
+}
+
+/// Returns the current shared pool of the redis data source named `name`.
+pub fn try_redis_pool_named(name: &str) -> Result<r2d2::Pool<RedisManager>, ConfigError> {
+    shared_redis_pool(&Backend::Redis.named_env_prefix(name)).map(|source| source.pool())
+}
+
+/// Statistics of the shared redis pool, which is built if needed.
+pub fn redis_pool_stats() -> PoolStats {
+    shared_redis_pool(Backend::Redis.env_prefix())
+        .unwrap_or_else(|e| panic!("{}", e))
+        .pool_stats()
+}
+
+fn shared_redis_pool(prefix: &str) -> Result<RedisDataSource, ConfigError> {
+    REDIS_POOLS.get_or_try_init(prefix, || {
+        let metrics = Arc::new(AcquireMetrics::default());
+        Ok(RedisDataSource {
+            current: Swap::new(build_pool(prefix, metrics.clone())?),
+            metrics,
+        })
+    })
+}
+
+/// Rebuilds the shared pools whose settings are among `keys` and swaps them
+/// in for every clone, returning their env prefixes. A pool that fails to
+/// build is kept.
+///
+/// The pools replaced close their connections once those in use are returned.
+pub(crate) fn reload(keys: &[String]) -> (Vec<String>, Vec<ConfigError>) {
+    let stale: Vec<_> = REDIS_POOLS
+        .built()
+        .into_iter()
+        .filter(|(prefix, _)| affected(prefix, keys))
+        .collect();
+    let mut rebuilt = Vec::new();
+    let mut errors = Vec::new();
+    for (prefix, source) in stale {
+        match build_pool(&prefix, source.metrics.clone()) {
+            Ok(pooled) => {
+                source.current.replace(pooled);
+                rebuilt.push(prefix);
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    (rebuilt, errors)
+}
+
+/// Builds the pool of `prefix`, counting its checkouts in `metrics`.
+fn build_pool(
+    prefix: &str,
+    metrics: Arc<AcquireMetrics>,
+) -> Result<Pooled<r2d2::Pool<RedisManager>>, ConfigError> {
+    env::load()?;
+    let key = format!("{}_URL", prefix);
+    let (redis_url, manager) = manager(prefix, &key)?;
+    let cfg = RedisPoolConfig::from_env(prefix)?;
+    let pool = cfg
+        .builder()
+        .event_handler(Box::new(MetricsHandler(metrics)))
+        .build(manager)
+        .map_err(|e| ConfigError::connection(&key, e))?;
+    Ok(Pooled {
+        url: redis_url,
+        pool,
+    })
+}
+
+/// Reads `{prefix}_URL`, or asks Sentinel for the master when
+/// `{prefix}_SENTINELS` is set. Sentinel is asked again for every new
+/// connection, so the pool follows failovers.
+fn manager(prefix: &str, key: &str) -> Result<(String, RedisManager), ConfigError> {
+    if let Some(sentinel) = Sentinel::from_env(prefix)? {
+        let url = sentinel.master_url(&sentinel.master_addr()?)?;
+        let target = Target::Sentinel(sentinel);
+        return Ok((url, RedisManager { target }));
+    }
+    let url = env::backend_url(Backend::Redis, prefix)?;
+    let client = redis::Client::open(url.as_str()).map_err(|e| ConfigError::MalformedUrl {
+        key: key.to_string(),
+        reason: e.to_string(),
+    })?;
+    let target = Target::Url(client);
+    Ok((url, RedisManager { target }))
+}
+
+pub fn get_redis_connection() -> PooledConnection<RedisManager> {
+    try_redis_pool()
+        .unwrap_or_else(|e| panic!("{}", e))
+        .get()
+        .unwrap()
+}
+
+/// Reads `REDIS_URL`, or `REDIS_SENTINELS` and `REDIS_MASTER_NAME` to find
+/// the master through Sentinel.
+pub fn redis_data_source() -> RedisDataSource {
+    try_redis_data_source().unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn try_redis_data_source() -> Result<RedisDataSource, ConfigError> {
+    shared_redis_pool(Backend::Redis.env_prefix())
+}
+
+/// Reads `{NAME}_REDIS_URL`, or `{NAME}_REDIS_SENTINELS` and
+/// `{NAME}_REDIS_MASTER_NAME`; the pool is shared by every caller using `name`.
+pub fn redis_data_source_named(name: &str) -> RedisDataSource {
+    try_redis_data_source_named(name).unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn try_redis_data_source_named(name: &str) -> Result<RedisDataSource, ConfigError> {
+    shared_redis_pool(&Backend::Redis.named_env_prefix(name))
+}
@@ -0,0 +1,102 @@
+//! Redis master for writes, local replica for reads, compiled with the `with-redis` feature.
+use std::fmt;
+
+use r2d2::PooledConnection;
+use redis::{ErrorKind, FromRedisValue, RedisError, RedisResult};
+
+use crate::env;
+use crate::error::ConfigError;
+use crate::redis_pool::{
+    try_redis_data_source, try_redis_data_source_named, RedisDataSource, RedisManager, MASTER,
+};
+
+/// 主从拓扑
+///
+/// Writes of core data, such as person, role and dept, go to the master
+/// read from `MASTER_REDIS_URL`, reads to the local replica read from
+/// `REDIS_URL`. Each pool is sized by its own `*_MAX_POOL_SIZE`, e.g.
+/// `MASTER_REDIS_MAX_POOL_SIZE`, defaulting to `REDIS_POOL_SIZE`.
+///
+/// ```no_run
+/// let topology = tdf_config::redis_topology();
+/// topology.write::<()>(redis::cmd("SET").arg("role:1").arg("admin"))?;
+/// let role: String = topology.read(redis::cmd("GET").arg("role:1"))?;
+/// # Ok::<(), redis::RedisError>(())
+/// ```
+#[derive(Clone)]
+pub struct RedisTopology {
+    master: RedisDataSource,
+    replica: RedisDataSource,
+}
+
+impl RedisTopology {
+    pub fn new(master: RedisDataSource, replica: RedisDataSource) -> Self {
+        RedisTopology { master, replica }
+    }
+
+    pub fn master(&self) -> &RedisDataSource {
+        &self.master
+    }
+
+    pub fn replica(&self) -> &RedisDataSource {
+        &self.replica
+    }
+
+    pub fn write_connection(&self) -> Result<PooledConnection<RedisManager>, r2d2::Error> {
+        self.master.get()
+    }
+
+    pub fn read_connection(&self) -> Result<PooledConnection<RedisManager>, r2d2::Error> {
+        self.replica.get()
+    }
+
+    /// Runs `cmd` on the master.
+    pub fn write<T: FromRedisValue>(&self, cmd: &redis::Cmd) -> RedisResult<T> {
+        query(&self.master, cmd)
+    }
+
+    /// Runs `cmd` on the replica.
+    pub fn read<T: FromRedisValue>(&self, cmd: &redis::Cmd) -> RedisResult<T> {
+        query(&self.replica, cmd)
+    }
+}
+
+fn query<T: FromRedisValue>(source: &RedisDataSource, cmd: &redis::Cmd) -> RedisResult<T> {
+    let mut conn = source.get().map_err(|e| {
+        RedisError::from((
+            ErrorKind::IoError,
+            "no redis connection available",
+            e.to_string(),
+        ))
+    })?;
+    cmd.query(&mut *conn)
+}
+
+impl fmt::Debug for RedisTopology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisTopology")
+            .field("master", &self.master.redacted_url())
+            .field("replica", &self.replica.redacted_url())
+            .finish()
+    }
+}
+
+pub fn redis_topology() -> RedisTopology {
+    try_redis_topology().unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Reads `MASTER_REDIS_URL` and `REDIS_URL`; when only `REDIS_URL` is set
+/// both reads and writes use it.
+pub fn try_redis_topology() -> Result<RedisTopology, ConfigError> {
+    env::load()?;
+    let replica = try_redis_data_source()?;
+    let master = if env::is_set("MASTER_REDIS_URL")
+        || env::is_set("MASTER_REDIS_HOST")
+        || env::is_set("MASTER_REDIS_SENTINELS")
+    {
+        try_redis_data_source_named(MASTER)?
+    } else {
+        replica.clone()
+    };
+    Ok(RedisTopology::new(master, replica))
+}
@@ -0,0 +1,450 @@
+//! Data sources built once, shared by every caller and drained on shutdown.
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use futures::future::join_all;
+use tokio::sync::Mutex;
+
+#[cfg(any(
+    feature = "with-mysql",
+    feature = "with-postgres",
+    feature = "with-sqlite"
+))]
+use crate::config::DataSourceConfig;
+use crate::error::ConfigError;
+use crate::named::Backend;
+use crate::reload::affected;
+use crate::stats::PoolStats;
+
+#[cfg(feature = "with-mysql")]
+use crate::mysql::{try_mysql_data_source, try_mysql_data_source_named, MySqlDataSource};
+#[cfg(feature = "with-postgres")]
+use crate::pg::{try_pg_data_source, try_pg_data_source_named, PgDataSource};
+#[cfg(feature = "with-redis")]
+use crate::redis_pool::{try_redis_data_source, try_redis_data_source_named, RedisDataSource};
+#[cfg(feature = "with-sqlite")]
+use crate::sqlite::{try_sqlite_data_source, try_sqlite_data_source_named, SqliteDataSource};
+
+/// How often closing a redis pool checks whether connections are still in use.
+#[cfg(feature = "with-redis")]
+const DRAIN_POLL: Duration = Duration::from_millis(50);
+
+lazy_static! {
+    /// Backs `mysql()`, `pg()` and `sqlite()`; drain it on SIGTERM.
+    pub static ref REGISTRY: Registry = Registry::new();
+}
+
+/// 数据源注册表
+///
+/// Keeps every data source it builds, keyed by env prefix, and hands out
+/// clones sharing its pool. A build that fails is not kept, so the next
+/// caller tries again.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), tdf_config::ConfigError> {
+/// use std::time::Duration;
+/// use tdf_config::REGISTRY;
+///
+/// let orders = REGISTRY.mysql_named("orders").await?;
+/// // ...
+/// let busy = REGISTRY.shutdown(Duration::from_secs(10)).await;
+/// assert!(busy.is_empty(), "still in use: {:?}", busy);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Registry {
+    sources: std::sync::Mutex<HashMap<String, Arc<Slot>>>,
+}
+
+/// The data source of one env prefix. `init` is held while it is built, so
+/// concurrent first callers wait for one build without blocking other prefixes.
+#[derive(Default)]
+struct Slot {
+    entry: RwLock<Option<Entry>>,
+    init: Mutex<()>,
+}
+
+impl Slot {
+    fn get(&self) -> Option<Entry> {
+        self.entry.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn set(&self, entry: Option<Entry>) -> Option<Entry> {
+        std::mem::replace(
+            &mut *self.entry.write().unwrap_or_else(|e| e.into_inner()),
+            entry,
+        )
+    }
+}
+
+#[derive(Clone)]
+pub enum Entry {
+    #[cfg(feature = "with-mysql")]
+    MySql(MySqlDataSource),
+    #[cfg(feature = "with-postgres")]
+    Pg(PgDataSource),
+    #[cfg(feature = "with-sqlite")]
+    Sqlite(SqliteDataSource),
+    #[cfg(feature = "with-redis")]
+    Redis(RedisDataSource),
+}
+
+impl Entry {
+    fn pool_stats(&self) -> PoolStats {
+        match self {
+            #[cfg(feature = "with-mysql")]
+            Entry::MySql(source) => source.pool_stats(),
+            #[cfg(feature = "with-postgres")]
+            Entry::Pg(source) => source.pool_stats(),
+            #[cfg(feature = "with-sqlite")]
+            Entry::Sqlite(source) => source.pool_stats(),
+            #[cfg(feature = "with-redis")]
+            Entry::Redis(source) => source.pool_stats(),
+        }
+    }
+
+    /// Stops handing out connections and resolves once those in use were
+    /// returned and closed.
+    ///
+    /// r2d2 pools cannot be closed, their connections are closed when the
+    /// last clone of the pool is dropped; this only waits until none is in use.
+    async fn close(&self) {
+        match self {
+            #[cfg(feature = "with-mysql")]
+            Entry::MySql(source) => source.pool().close().await,
+            #[cfg(feature = "with-postgres")]
+            Entry::Pg(source) => source.pool().close().await,
+            #[cfg(feature = "with-sqlite")]
+            Entry::Sqlite(source) => source.pool().close().await,
+            #[cfg(feature = "with-redis")]
+            Entry::Redis(source) => {
+                while source.pool_stats().in_use() > 0 {
+                    tokio::time::delay_for(DRAIN_POLL).await;
+                }
+            }
+        }
+    }
+
+    /// Builds a pool from the current settings of `prefix` and swaps it in
+    /// for every clone. The old pool is closed in the background, its
+    /// connections in use are closed when returned.
+    async fn rebuild(&self, prefix: &str) -> Result<(), ConfigError> {
+        match self {
+            #[cfg(feature = "with-mysql")]
+            Entry::MySql(source) => {
+                let new = MySqlDataSource::from_config(DataSourceConfig::from_env(prefix)?).await?;
+                let old = source.replace(new);
+                tokio::spawn(async move { old.close().await });
+                Ok(())
+            }
+            #[cfg(feature = "with-postgres")]
+            Entry::Pg(source) => {
+                let new = PgDataSource::from_config(DataSourceConfig::from_env(prefix)?).await?;
+                let old = source.replace(new);
+                tokio::spawn(async move { old.close().await });
+                Ok(())
+            }
+            #[cfg(feature = "with-sqlite")]
+            Entry::Sqlite(source) => {
+                let new =
+                    SqliteDataSource::from_config(DataSourceConfig::from_env(prefix)?).await?;
+                let old = source.replace(new);
+                tokio::spawn(async move { old.close().await });
+                Ok(())
+            }
+            #[cfg(feature = "with-redis")]
+            Entry::Redis(_) => unreachable!("{} is rebuilt by redis_pool::reload", prefix),
+        }
+    }
+
+    /// Whether `Registry::reload` rebuilds it, redis pools are left to `redis_pool::reload`.
+    fn reloadable(&self) -> bool {
+        match self {
+            #[cfg(feature = "with-redis")]
+            Entry::Redis(_) => false,
+            #[allow(unreachable_patterns)]
+            _ => true,
+        }
+    }
+}
+
+/// The data source types a `Registry` can hold.
+///
+/// It is public only to appear in `Registry::insert`, its module is private
+/// so it cannot be implemented outside this crate.
+pub trait Registered: Clone {
+    fn into_entry(self) -> Entry;
+    fn from_entry(entry: &Entry) -> Option<Self>;
+}
+
+#[cfg(feature = "with-mysql")]
+impl Registered for MySqlDataSource {
+    fn into_entry(self) -> Entry {
+        Entry::MySql(self)
+    }
+
+    fn from_entry(entry: &Entry) -> Option<Self> {
+        match entry {
+            Entry::MySql(source) => Some(source.clone()),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "with-postgres")]
+impl Registered for PgDataSource {
+    fn into_entry(self) -> Entry {
+        Entry::Pg(self)
+    }
+
+    fn from_entry(entry: &Entry) -> Option<Self> {
+        match entry {
+            Entry::Pg(source) => Some(source.clone()),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "with-sqlite")]
+impl Registered for SqliteDataSource {
+    fn into_entry(self) -> Entry {
+        Entry::Sqlite(self)
+    }
+
+    fn from_entry(entry: &Entry) -> Option<Self> {
+        match entry {
+            Entry::Sqlite(source) => Some(source.clone()),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "with-redis")]
+impl Registered for RedisDataSource {
+    fn into_entry(self) -> Entry {
+        Entry::Redis(self)
+    }
+
+    fn from_entry(entry: &Entry) -> Option<Self> {
+        match entry {
+            Entry::Redis(source) => Some(source.clone()),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry {
+            sources: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[cfg(feature = "with-mysql")]
+    pub async fn mysql(&self) -> Result<MySqlDataSource, ConfigError> {
+        self.get_or_try_init(Backend::MySql.env_prefix(), try_mysql_data_source)
+            .await
+    }
+
+    #[cfg(feature = "with-mysql")]
+    pub async fn mysql_named(&self, name: &str) -> Result<MySqlDataSource, ConfigError> {
+        self.get_or_try_init(&Backend::MySql.named_env_prefix(name), || {
+            try_mysql_data_source_named(name)
+        })
+        .await
+    }
+
+    #[cfg(feature = "with-postgres")]
+    pub async fn pg(&self) -> Result<PgDataSource, ConfigError> {
+        self.get_or_try_init(Backend::Pg.env_prefix(), try_pg_data_source)
+            .await
+    }
+
+    #[cfg(feature = "with-postgres")]
+    pub async fn pg_named(&self, name: &str) -> Result<PgDataSource, ConfigError> {
+        self.get_or_try_init(&Backend::Pg.named_env_prefix(name), || {
+            try_pg_data_source_named(name)
+        })
+        .await
+    }
+
+    #[cfg(feature = "with-sqlite")]
+    pub async fn sqlite(&self) -> Result<SqliteDataSource, ConfigError> {
+        self.get_or_try_init(Backend::Sqlite.env_prefix(), try_sqlite_data_source)
+            .await
+    }
+
+    #[cfg(feature = "with-sqlite")]
+    pub async fn sqlite_named(&self, name: &str) -> Result<SqliteDataSource, ConfigError> {
+        self.get_or_try_init(&Backend::Sqlite.named_env_prefix(name), || {
+            try_sqlite_data_source_named(name)
+        })
+        .await
+    }
+
+    #[cfg(feature = "with-redis")]
+    pub async fn redis(&self) -> Result<RedisDataSource, ConfigError> {
+        let prefix = Backend::Redis.env_prefix();
+        self.get_or_try_init(prefix, || blocking(prefix, try_redis_data_source))
+            .await
+    }
+
+    #[cfg(feature = "with-redis")]
+    pub async fn redis_named(&self, name: &str) -> Result<RedisDataSource, ConfigError> {
+        let prefix = Backend::Redis.named_env_prefix(name);
+        let name = name.to_string();
+        self.get_or_try_init(&prefix, || {
+            blocking(&prefix, move || try_redis_data_source_named(&name))
+        })
+        .await
+    }
+
+    /// Keeps `source`, built by the caller, under the env prefix it was read
+    /// from, e.g. `ORDERS_MYSQL`, so it is reloaded and drained like those the
+    /// registry builds. A data source already kept under `prefix` is replaced,
+    /// not closed.
+    pub async fn insert<T: Registered>(&self, prefix: &str, source: T) {
+        let slot = self.slot(prefix);
+        let _init = slot.init.lock().await;
+        slot.set(Some(source.into_entry()));
+    }
+
+    /// Env prefixes of the data sources built so far.
+    pub async fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self
+            .entries()
+            .into_iter()
+            .map(|(prefix, _)| prefix)
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Statistics of every pool, keyed by env prefix, e.g. for `prometheus_text`.
+    pub async fn pool_stats(&self) -> Vec<(String, PoolStats)> {
+        let mut stats: Vec<_> = self
+            .entries()
+            .into_iter()
+            .map(|(prefix, entry)| (prefix, entry.pool_stats()))
+            .collect();
+        stats.sort_by(|a, b| a.0.cmp(&b.0));
+        stats
+    }
+
+    /// Closes every pool and waits up to `timeout` for connections in use
+    /// to be returned.
+    ///
+    /// Returns the env prefixes whose connections were still in use when the
+    /// timeout expired, empty when everything drained. The registry is empty
+    /// afterwards; asking it for a data source again builds a new pool.
+    pub async fn shutdown(&self, timeout: Duration) -> Vec<String> {
+        let slots: Vec<_> = self
+            .sources
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .drain()
+            .collect();
+        let closes = slots.into_iter().filter_map(|(prefix, slot)| {
+            let entry = slot.set(None)?;
+            Some(async move {
+                match tokio::time::timeout(timeout, entry.close()).await {
+                    Ok(()) => None,
+                    Err(_) => Some(prefix),
+                }
+            })
+        });
+        let mut busy: Vec<_> = join_all(closes).await.into_iter().flatten().collect();
+        busy.sort();
+        busy
+    }
+
+    /// Rebuilds the pools of the data sources whose settings are among `keys`
+    /// and swaps them in for every clone, returning their env prefixes. A
+    /// data source whose pool fails to build keeps the old one.
+    pub(crate) async fn reload(&self, keys: &[String]) -> (Vec<String>, Vec<ConfigError>) {
+        let stale: Vec<_> = self
+            .entries()
+            .into_iter()
+            .filter(|(prefix, entry)| entry.reloadable() && affected(prefix, keys))
+            .collect();
+        let mut rebuilt = Vec::new();
+        let mut errors = Vec::new();
+        for (prefix, entry) in stale {
+            match entry.rebuild(&prefix).await {
+                Ok(()) => rebuilt.push(prefix),
+                Err(e) => errors.push(e),
+            }
+        }
+        (rebuilt, errors)
+    }
+
+    /// The data sources built so far, with their env prefix.
+    fn entries(&self) -> Vec<(String, Entry)> {
+        let slots: Vec<_> = self
+            .sources
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(prefix, slot)| (prefix.clone(), slot.clone()))
+            .collect();
+        slots
+            .into_iter()
+            .filter_map(|(prefix, slot)| Some((prefix, slot.get()?)))
+            .collect()
+    }
+
+    fn slot(&self, prefix: &str) -> Arc<Slot> {
+        self.sources
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(prefix.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Returns a clone of the data source for `prefix`, building it with
+    /// `build` on first use. Concurrent first callers wait for one build,
+    /// callers of other prefixes do not.
+    async fn get_or_try_init<T, F, Fut>(&self, prefix: &str, build: F) -> Result<T, ConfigError>
+    where
+        T: Registered,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, ConfigError>>,
+    {
+        let slot = self.slot(prefix);
+        if let Some(source) = slot.get().as_ref().and_then(T::from_entry) {
+            return Ok(source);
+        }
+        let _init = slot.init.lock().await;
+        if let Some(source) = slot.get().as_ref().and_then(T::from_entry) {
+            return Ok(source);
+        }
+        let source = build().await?;
+        slot.set(Some(source.clone().into_entry()));
+        Ok(source)
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry::new()
+    }
+}
+
+/// Builds a redis data source on tokio's blocking threads, as r2d2 connects
+/// while building the pool.
+#[cfg(feature = "with-redis")]
+async fn blocking<F>(prefix: &str, build: F) -> Result<RedisDataSource, ConfigError>
+where
+    F: FnOnce() -> Result<RedisDataSource, ConfigError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(build)
+        .await
+        .map_err(|e| ConfigError::connection(&format!("{}_URL", prefix), e.to_string()))?
+}
@@ -0,0 +1,159 @@
+//! Reloading `.env` and the config files while running.
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use tokio::task::JoinHandle;
+
+use crate::env;
+use crate::error::ConfigError;
+#[cfg(any(
+    feature = "with-mysql",
+    feature = "with-postgres",
+    feature = "with-sqlite",
+    feature = "with-redis"
+))]
+use crate::registry::REGISTRY;
+
+/// 配置变更
+#[derive(Debug, Default)]
+pub struct ConfigChange {
+    /// Keys whose value changed, was added or was removed.
+    pub keys: Vec<String>,
+    /// Env prefixes of the pools rebuilt and swapped in for every clone.
+    pub rebuilt: Vec<String>,
+    /// Pools that failed to build, the old ones are kept.
+    pub errors: Vec<ConfigError>,
+}
+
+type Listener = Box<dyn Fn(&ConfigChange) + Send + Sync>;
+
+lazy_static! {
+    static ref LISTENERS: Mutex<Vec<Listener>> = Mutex::new(Vec::new());
+}
+
+/// Calls `listener` after every reload that changed a key.
+pub fn on_change(listener: impl Fn(&ConfigChange) + Send + Sync + 'static) {
+    LISTENERS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Box::new(listener));
+}
+
+/// Reads `.env` and the config files again and rebuilds the pools whose
+/// settings changed.
+///
+/// Only pools built through `REGISTRY`, `try_redis_pool` or the redis data
+/// source and cluster functions are rebuilt, and swapped in for every clone;
+/// those built by `mysql_data_source()` and friends are owned by the caller.
+/// Keys of the real environment and secrets read from `{KEY}_FILE` are not
+/// watched.
+pub async fn reload() -> Result<ConfigChange, ConfigError> {
+    let keys = env::reload()?;
+    if keys.is_empty() {
+        return Ok(ConfigChange::default());
+    }
+    Ok(rebuild(keys).await)
+}
+
+/// Rebuilds the pools configured by `keys` and notifies the listeners.
+async fn rebuild(keys: Vec<String>) -> ConfigChange {
+    let mut change = ConfigChange {
+        keys,
+        ..ConfigChange::default()
+    };
+    #[cfg(feature = "with-redis")]
+    {
+        // r2d2 connects while building, so off the runtime's threads.
+        let keys = change.keys.clone();
+        let reloaded = tokio::task::spawn_blocking(move || {
+            [
+                crate::redis_pool::reload(&keys),
+                crate::redis_cluster::reload(&keys),
+            ]
+        })
+        .await;
+        match reloaded {
+            Ok(reloaded) => {
+                for (rebuilt, errors) in reloaded {
+                    change.rebuilt.extend(rebuilt);
+                    change.errors.extend(errors);
+                }
+            }
+            Err(e) => log::error!("failed to reload the redis pools: {}", e),
+        }
+    }
+    #[cfg(any(
+        feature = "with-mysql",
+        feature = "with-postgres",
+        feature = "with-sqlite",
+        feature = "with-redis"
+    ))]
+    {
+        let (rebuilt, errors) = REGISTRY.reload(&change.keys).await;
+        change.rebuilt.extend(rebuilt);
+        change.errors.extend(errors);
+    }
+    change.rebuilt.sort();
+    change.rebuilt.dedup();
+    for e in &change.errors {
+        log::error!("keeping the old pool: {}", e);
+    }
+    for listener in LISTENERS.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+        listener(&change);
+    }
+    change
+}
+
+/// Checks the files every `interval` and reloads when one of them changed.
+///
+/// ```no_run
+/// # async fn run() {
+/// use std::time::Duration;
+///
+/// tdf_config::on_change(|change| println!("reloaded {:?}", change.rebuilt));
+/// tdf_config::watch(Duration::from_secs(5));
+/// # }
+/// ```
+pub fn watch(interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut seen = modified();
+        loop {
+            tokio::time::delay_for(interval).await;
+            let now = modified();
+            if now == seen {
+                continue;
+            }
+            seen = now;
+            if let Err(e) = reload().await {
+                log::error!("failed to reload the configuration: {}", e);
+            }
+        }
+    })
+}
+
+fn modified() -> Vec<(PathBuf, Option<SystemTime>)> {
+    env::watched_files()
+        .into_iter()
+        .map(|path| {
+            let modified = std::fs::metadata(&path)
+                .and_then(|meta| meta.modified())
+                .ok();
+            (path, modified)
+        })
+        .collect()
+}
+
+/// Whether any of `keys` configures the data source read from `prefix`.
+#[cfg(any(
+    feature = "with-mysql",
+    feature = "with-postgres",
+    feature = "with-sqlite",
+    feature = "with-redis"
+))]
+pub(crate) fn affected(prefix: &str, keys: &[String]) -> bool {
+    keys.iter().any(|key| {
+        key.strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('_'))
+    })
+}
@@ -0,0 +1,256 @@
+//! Read/write split over a primary and its replicas.
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use sqlx::pool::PoolConnection;
+use sqlx::Connect;
+
+use crate::config::DataSourceConfig;
+use crate::env;
+use crate::error::ConfigError;
+use crate::named::Backend;
+use crate::redact::redact_url;
+use crate::retry::RetryPolicy;
+use crate::DataSource;
+
+#[cfg(feature = "with-mysql")]
+use crate::mysql::MySqlDataSource;
+#[cfg(feature = "with-postgres")]
+use crate::pg::PgDataSource;
+
+/// How `reader()` picks a replica.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplicaSelection {
+    /// Each replica in turn.
+    #[default]
+    RoundRobin,
+    /// The replica with the fewest connections in use.
+    LeastLoaded,
+}
+
+impl FromStr for ReplicaSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "round-robin" => Ok(ReplicaSelection::RoundRobin),
+            "least-loaded" => Ok(ReplicaSelection::LeastLoaded),
+            _ => Err("expected round-robin or least-loaded".to_string()),
+        }
+    }
+}
+
+/// 读写分离数据源
+///
+/// Writes go to the primary, reads to a replica, or to the primary when
+/// there is none. Clones share the pools and the round-robin position.
+#[derive(Clone)]
+pub struct Replicated<S> {
+    primary: S,
+    replicas: Vec<S>,
+    selection: ReplicaSelection,
+    next: Arc<AtomicUsize>,
+}
+
+#[cfg(feature = "with-mysql")]
+pub type ReplicatedMySqlDataSource = Replicated<MySqlDataSource>;
+#[cfg(feature = "with-postgres")]
+pub type ReplicatedPgDataSource = Replicated<PgDataSource>;
+
+impl<S> Replicated<S>
+where
+    S: DataSource + Clone + Send,
+    S::C: Connect,
+{
+    pub fn new(primary: S, replicas: Vec<S>, selection: ReplicaSelection) -> Self {
+        Replicated {
+            primary,
+            replicas,
+            selection,
+            next: Arc::default(),
+        }
+    }
+
+    /// The primary, for writes and reads that must see them.
+    pub fn writer(&self) -> &S {
+        &self.primary
+    }
+
+    /// A replica picked by the selection policy, the primary if there is none.
+    pub fn reader(&self) -> &S {
+        self.reader_index()
+            .map_or(&self.primary, |index| &self.replicas[index])
+    }
+
+    pub fn replicas(&self) -> &[S] {
+        &self.replicas
+    }
+
+    /// Takes a connection from the replica `reader()` picks, trying the
+    /// other replicas, then the primary, when that fails.
+    pub async fn acquire_reader(&self) -> sqlx::Result<PoolConnection<S::C>> {
+        if let Some(first) = self.reader_index() {
+            let count = self.replicas.len();
+            for index in (0..count).map(|offset| (first + offset) % count) {
+                match self.replicas[index].acquire().await {
+                    Ok(conn) => return Ok(conn),
+                    Err(e) => log::warn!(
+                        "replica {} failed, trying the next: {}",
+                        redact_url(&self.replicas[index].get_url()),
+                        e
+                    ),
+                }
+            }
+        }
+        self.primary.acquire().await
+    }
+
+    fn reader_index(&self) -> Option<usize> {
+        if self.replicas.is_empty() {
+            return None;
+        }
+        let index = match self.selection {
+            ReplicaSelection::RoundRobin => {
+                self.next.fetch_add(1, Ordering::Relaxed) % self.replicas.len()
+            }
+            ReplicaSelection::LeastLoaded => (0..self.replicas.len())
+                .min_by_key(|&index| self.replicas[index].pool_stats().in_use())
+                .unwrap_or_default(),
+        };
+        Some(index)
+    }
+}
+
+impl<S: DataSource> fmt::Debug for Replicated<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let replicas: Vec<_> = self
+            .replicas
+            .iter()
+            .map(|replica| redact_url(&replica.get_url()))
+            .collect();
+        f.debug_struct("Replicated")
+            .field("primary", &redact_url(&self.primary.get_url()))
+            .field("replicas", &replicas)
+            .field("selection", &self.selection)
+            .finish()
+    }
+}
+
+/// Reads the primary like `DataSourceConfig::from_env(prefix)`, then the
+/// comma separated `{prefix}_REPLICA_URLS` and `{prefix}_REPLICA_SELECTION`.
+///
+/// Replicas share the primary's pool settings, errors about their URL name
+/// `{prefix}_REPLICA_URLS`. One that cannot be reached is kept with an empty
+/// pool, which connects on first use, so reads skip it until it is back.
+async fn from_env<S, F, Fut>(prefix: &str, from_config: F) -> Result<Replicated<S>, ConfigError>
+where
+    S: DataSource + Clone + Send,
+    S::C: Connect,
+    F: Fn(DataSourceConfig) -> Fut,
+    Fut: Future<Output = Result<S, ConfigError>>,
+{
+    let cfg = DataSourceConfig::from_env(prefix)?;
+    let key = format!("{}_REPLICA_URLS", prefix);
+    let urls = env::opt_var(&key)?.unwrap_or_default();
+    let urls: Vec<_> = urls
+        .split(',')
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .collect();
+    for url in &urls {
+        url::Url::parse(url).map_err(|e| ConfigError::MalformedUrl {
+            key: key.clone(),
+            reason: e.to_string(),
+        })?;
+    }
+    let selection = env::parse(
+        &format!("{}_REPLICA_SELECTION", prefix),
+        ReplicaSelection::default(),
+    )?;
+    let primary = from_config(cfg.clone()).await?;
+    let mut replicas = Vec::with_capacity(urls.len());
+    for url in urls {
+        let replica_cfg = cfg.clone().url_from(url, &key);
+        let replica = match from_config(replica_cfg.clone()).await {
+            Ok(replica) => replica,
+            Err(e) => {
+                log::warn!("replica {} is unreachable: {}", redact_url(url), e);
+                // With no idle connections to open the pool is built without connecting.
+                let lazy = replica_cfg.min_size(0).retry(RetryPolicy {
+                    retries: 0,
+                    ..cfg.get_retry()
+                });
+                from_config(lazy).await?
+            }
+        };
+        replicas.push(replica);
+    }
+    Ok(Replicated::new(primary, replicas, selection))
+}
+
+/// Reads `MYSQL_URL` and `MYSQL_REPLICA_URLS`.
+#[cfg(feature = "with-mysql")]
+pub async fn replicated_mysql_data_source() -> ReplicatedMySqlDataSource {
+    try_replicated_mysql_data_source()
+        .await
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+#[cfg(feature = "with-mysql")]
+pub async fn try_replicated_mysql_data_source() -> Result<ReplicatedMySqlDataSource, ConfigError> {
+    from_env(Backend::MySql.env_prefix(), MySqlDataSource::from_config).await
+}
+
+/// Reads `{NAME}_MYSQL_URL` and `{NAME}_MYSQL_REPLICA_URLS`.
+#[cfg(feature = "with-mysql")]
+pub async fn replicated_mysql_data_source_named(name: &str) -> ReplicatedMySqlDataSource {
+    try_replicated_mysql_data_source_named(name)
+        .await
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+#[cfg(feature = "with-mysql")]
+pub async fn try_replicated_mysql_data_source_named(
+    name: &str,
+) -> Result<ReplicatedMySqlDataSource, ConfigError> {
+    from_env(
+        &Backend::MySql.named_env_prefix(name),
+        MySqlDataSource::from_config,
+    )
+    .await
+}
+
+/// Reads `PG_URL` and `PG_REPLICA_URLS`.
+#[cfg(feature = "with-postgres")]
+pub async fn replicated_pg_data_source() -> ReplicatedPgDataSource {
+    try_replicated_pg_data_source()
+        .await
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+#[cfg(feature = "with-postgres")]
+pub async fn try_replicated_pg_data_source() -> Result<ReplicatedPgDataSource, ConfigError> {
+    from_env(Backend::Pg.env_prefix(), PgDataSource::from_config).await
+}
+
+/// Reads `{NAME}_PG_URL` and `{NAME}_PG_REPLICA_URLS`.
+#[cfg(feature = "with-postgres")]
+pub async fn replicated_pg_data_source_named(name: &str) -> ReplicatedPgDataSource {
+    try_replicated_pg_data_source_named(name)
+        .await
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+#[cfg(feature = "with-postgres")]
+pub async fn try_replicated_pg_data_source_named(
+    name: &str,
+) -> Result<ReplicatedPgDataSource, ConfigError> {
+    from_env(
+        &Backend::Pg.named_env_prefix(name),
+        PgDataSource::from_config,
+    )
+    .await
+}
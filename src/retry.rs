@@ -0,0 +1,81 @@
+//! Retrying the first connection while the backend is still starting.
+use std::time::Duration;
+
+use crate::env;
+use crate::error::ConfigError;
+
+/// Default delay before the first retry.
+pub const CONNECT_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Default cap on the delay between retries.
+pub const MAX_CONNECT_BACKOFF: Duration = Duration::from_secs(30);
+
+/// 重试策略
+///
+/// The delay doubles after every failed attempt, up to `max_backoff`. With
+/// jitter on, each delay is drawn from its upper half, so services started
+/// together do not retry in lockstep. No retries are made by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 0,
+            backoff: CONNECT_BACKOFF,
+            max_backoff: MAX_CONNECT_BACKOFF,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Reads `{prefix}_CONNECT_RETRIES`, `{prefix}_CONNECT_BACKOFF_MS`,
+    /// `{prefix}_CONNECT_MAX_BACKOFF_MS` and `{prefix}_CONNECT_JITTER`.
+    pub fn from_env(prefix: &str) -> Result<Self, ConfigError> {
+        let key = |suffix: &str| format!("{}_CONNECT_{}", prefix, suffix);
+        let default = RetryPolicy::default();
+        Ok(RetryPolicy {
+            retries: env::parse(&key("RETRIES"), default.retries)?,
+            backoff: Duration::from_millis(env::parse(
+                &key("BACKOFF_MS"),
+                default.backoff.as_millis() as u64,
+            )?),
+            max_backoff: Duration::from_millis(env::parse(
+                &key("MAX_BACKOFF_MS"),
+                default.max_backoff.as_millis() as u64,
+            )?),
+            jitter: env::parse(&key("JITTER"), default.jitter)?,
+        })
+    }
+
+    /// Delay before retry number `retry`, counted from 0, without jitter.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 1u32.checked_shl(retry).unwrap_or(u32::MAX);
+        self.backoff
+            .checked_mul(factor)
+            .map_or(self.max_backoff, |delay| delay.min(self.max_backoff))
+    }
+
+    #[cfg(any(
+        feature = "with-mysql",
+        feature = "with-postgres",
+        feature = "with-sqlite"
+    ))]
+    pub(crate) fn jittered_delay(&self, retry: u32) -> Duration {
+        let delay = self.delay(retry);
+        if !self.jitter {
+            return delay;
+        }
+        // Clock noise is random enough to spread out retries.
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |now| now.subsec_nanos());
+        delay / 2 + delay.mul_f64(f64::from(nanos % 1000) / 2000.0)
+    }
+}
@@ -0,0 +1,152 @@
+//! Redis Sentinel discovery, compiled with the `with-redis` feature.
+use std::time::Duration;
+
+use redis::{ErrorKind, RedisError, RedisResult};
+
+use crate::env;
+use crate::error::ConfigError;
+use crate::params::ConnectParams;
+
+/// How long to wait for each sentinel.
+const SENTINEL_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The sentinels of one redis data source.
+pub(crate) struct Sentinel {
+    key: String,
+    addrs: Vec<String>,
+    master_name: String,
+    password: Option<String>,
+    template: ConnectParams,
+}
+
+impl Sentinel {
+    /// Reads `{prefix}_SENTINELS`, `{prefix}_MASTER_NAME` and
+    /// `{prefix}_SENTINEL_PASSWORD`; `None` when `{prefix}_SENTINELS` is unset.
+    ///
+    /// The user, password and database of the master are taken from
+    /// `{prefix}_URL` when set, else from `{prefix}_USER`, `{prefix}_PASSWORD`
+    /// and `{prefix}_DATABASE`.
+    pub(crate) fn from_env(prefix: &str) -> Result<Option<Self>, ConfigError> {
+        let key = |suffix: &str| format!("{}_{}", prefix, suffix);
+        let addrs = match env::opt_var(&key("SENTINELS"))? {
+            None => return Ok(None),
+            Some(addrs) => addrs
+                .split(',')
+                .map(str::trim)
+                .filter(|addr| !addr.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>(),
+        };
+        if addrs.is_empty() {
+            return Err(ConfigError::InvalidValue {
+                key: key("SENTINELS"),
+                reason: "expected host:port,host:port".to_string(),
+            });
+        }
+        let template = match env::opt_var(&key("URL"))? {
+            Some(url) => ConnectParams::from_url(&url).map_err(|e| ConfigError::MalformedUrl {
+                key: key("URL"),
+                reason: e.to_string(),
+            })?,
+            None => ConnectParams {
+                scheme: "redis".to_string(),
+                host: String::new(),
+                port: None,
+                user: env::opt_var(&key("USER"))?,
+                password: env::opt_var(&key("PASSWORD"))?,
+                database: env::opt_var(&key("DATABASE"))?,
+                ssl_mode: None,
+            },
+        };
+        Ok(Some(Sentinel {
+            key: key("SENTINELS"),
+            addrs,
+            master_name: env::var(&key("MASTER_NAME"))?,
+            password: env::opt_var(&key("SENTINEL_PASSWORD"))?,
+            template,
+        }))
+    }
+
+    /// Asks each sentinel in turn for the address of the current master.
+    pub(crate) fn master_addr(&self) -> Result<(String, u16), ConfigError> {
+        let mut last_error = None;
+        for addr in &self.addrs {
+            match self.ask(addr) {
+                Ok(Some(master)) => return Ok(master),
+                Ok(None) => {
+                    last_error = Some(format!(
+                        "{} does not know master {:?}",
+                        addr, self.master_name
+                    ))
+                }
+                Err(e) => last_error = Some(format!("{}: {}", addr, e)),
+            }
+        }
+        Err(ConfigError::connection(
+            &self.key,
+            last_error.unwrap_or_default(),
+        ))
+    }
+
+    fn ask(&self, addr: &str) -> RedisResult<Option<(String, u16)>> {
+        let mut url = url::Url::parse(&format!("redis://{}", addr)).map_err(|e| {
+            RedisError::from((
+                ErrorKind::InvalidClientConfig,
+                "invalid sentinel address",
+                e.to_string(),
+            ))
+        })?;
+        if let Some(password) = &self.password {
+            url.set_password(Some(password)).ok();
+        }
+        let mut conn =
+            redis::Client::open(url.as_str())?.get_connection_with_timeout(SENTINEL_TIMEOUT)?;
+        redis::cmd("SENTINEL")
+            .arg("get-master-addr-by-name")
+            .arg(&self.master_name)
+            .query(&mut conn)
+    }
+
+    /// Connects to the master the sentinels report now.
+    pub(crate) fn connect(&self) -> RedisResult<redis::Connection> {
+        let url = self
+            .master_addr()
+            .and_then(|master| self.master_url(&master))
+            .map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "no redis master found", e.to_string()))
+            })?;
+        redis::Client::open(url.as_str())?.get_connection()
+    }
+
+    /// The URL of the master at `addr`.
+    pub(crate) fn master_url(&self, (host, port): &(String, u16)) -> Result<String, ConfigError> {
+        let params = ConnectParams {
+            host: host.clone(),
+            port: Some(*port),
+            ..self.template.clone()
+        };
+        params.to_url().map_err(|e| ConfigError::MalformedUrl {
+            key: self.key.clone(),
+            reason: e.to_string(),
+        })
+    }
+}
+
+/// Fails unless `conn` is connected to a master, as it no longer is once
+/// Sentinel promoted a replica.
+pub(crate) fn check_master(conn: &mut redis::Connection) -> RedisResult<()> {
+    let reply: Vec<redis::Value> = redis::cmd("ROLE").query(conn)?;
+    let role: String = match reply.first() {
+        Some(role) => redis::from_redis_value(role)?,
+        None => String::new(),
+    };
+    if role == "master" {
+        Ok(())
+    } else {
+        Err(RedisError::from((
+            ErrorKind::ResponseError,
+            "not the redis master",
+            role,
+        )))
+    }
+}
@@ -0,0 +1,148 @@
+//! The data source of the SQL backends, each enabled by its own feature.
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+
+use futures::future::BoxFuture;
+use sqlx::pool::PoolConnection;
+use sqlx::Connect;
+
+use crate::config::DataSourceConfig;
+use crate::error::ConfigError;
+use crate::health::HealthStatus;
+use crate::redact::redact_url;
+use crate::stats::{AcquireMetrics, PoolStats};
+use crate::swap::{Pooled, Swap};
+use crate::DataSource;
+
+/// What differs between the SQL backends, implemented by the connection
+/// type of each. Its module is private, so it cannot be implemented outside
+/// this crate.
+pub trait SqlConnection: Connect + Sized {
+    /// Name of the data source in its Debug output.
+    const DATA_SOURCE: &'static str;
+
+    /// Adjusts the pool settings before the pool is built.
+    fn prepare(cfg: DataSourceConfig) -> DataSourceConfig {
+        cfg
+    }
+
+    /// Runs `SELECT 1`.
+    fn ping(pool: &sqlx::Pool<Self>) -> BoxFuture<'_, sqlx::Result<()>>;
+
+    fn server_version(pool: &sqlx::Pool<Self>) -> BoxFuture<'_, Option<String>>;
+}
+
+/// SQL 数据源
+///
+/// Clones share the pool, which `reload` swaps for all of them. Used
+/// through `MySqlDataSource`, `PgDataSource` and `SqliteDataSource`.
+pub struct SqlDataSource<C> {
+    current: Swap<Pooled<sqlx::Pool<C>>>,
+    metrics: Arc<AcquireMetrics>,
+}
+
+impl<C: SqlConnection> SqlDataSource<C> {
+    pub async fn from_config(cfg: DataSourceConfig) -> Result<Self, ConfigError> {
+        let cfg = C::prepare(cfg);
+        let pool = cfg.build_pool::<C>().await?;
+        Ok(SqlDataSource {
+            current: Swap::new(Pooled {
+                url: cfg.get_url().to_string(),
+                pool,
+            }),
+            metrics: Arc::default(),
+        })
+    }
+
+    /// The current pool; clone it again after a reload to use the new one.
+    pub fn pool(&self) -> sqlx::Pool<C> {
+        self.current.get().pool
+    }
+
+    /// Swaps in the URL and pool of `other` for every clone, returning the
+    /// pool replaced.
+    pub(crate) fn replace(&self, other: Self) -> sqlx::Pool<C> {
+        self.current.replace(other.current.get()).pool
+    }
+
+    /// The URL with its password masked, safe to log.
+    pub fn redacted_url(&self) -> String {
+        redact_url(&self.current.get().url)
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        let pool = self.pool();
+        self.metrics
+            .stats(pool.size(), pool.idle() as u32, pool.max_size())
+    }
+
+    /// Takes a connection from the pool, counting the wait in `pool_stats`.
+    pub async fn acquire(&self) -> sqlx::Result<PoolConnection<C>> {
+        let started = Instant::now();
+        let conn = self.pool().acquire().await;
+        self.metrics.record(started.elapsed(), conn.is_ok());
+        conn
+    }
+}
+
+impl<C> Clone for SqlDataSource<C> {
+    fn clone(&self) -> Self {
+        SqlDataSource {
+            current: self.current.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+// The pool's own Debug output includes the URL, so only its counters are shown.
+impl<C: SqlConnection> fmt::Debug for SqlDataSource<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct(C::DATA_SOURCE)
+            .field("url", &self.redacted_url())
+            .field("size", &self.pool().size())
+            .field("idle", &self.pool().idle())
+            .finish()
+    }
+}
+
+impl<C: SqlConnection> fmt::Display for SqlDataSource<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.redacted_url())
+    }
+}
+
+impl<C: SqlConnection> DataSource for SqlDataSource<C> {
+    type C = C;
+
+    fn get_url(&self) -> String {
+        self.current.get().url
+    }
+
+    fn get_pool(&mut self) -> sqlx::Pool<C> {
+        self.pool()
+    }
+
+    fn health_check(&self) -> BoxFuture<'_, HealthStatus> {
+        Box::pin(async move {
+            let started = Instant::now();
+            let pool = self.pool();
+            let ping = C::ping(&pool).await;
+            let latency = started.elapsed();
+            match ping {
+                Ok(()) => {
+                    HealthStatus::up(latency, C::server_version(&pool).await, self.pool_stats())
+                }
+                Err(e) => HealthStatus::down(latency, self.pool_stats(), e),
+            }
+        })
+    }
+
+    fn pool_stats(&self) -> PoolStats {
+        SqlDataSource::pool_stats(self)
+    }
+
+    fn acquire(&self) -> BoxFuture<'_, sqlx::Result<PoolConnection<C>>> {
+        Box::pin(SqlDataSource::acquire(self))
+    }
+}
@@ -0,0 +1,88 @@
+//! SQLite data source, compiled with the `with-sqlite` feature.
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use sqlx::prelude::*;
+use sqlx::{SqliteConnection, SqlitePool};
+
+use crate::config::DataSourceConfig;
+use crate::error::ConfigError;
+use crate::named::Backend;
+use crate::registry::REGISTRY;
+use crate::sql::{SqlConnection, SqlDataSource};
+
+/// Opens a file database such as `sqlite://data.db`, or an in-memory one
+/// with `sqlite::memory:`.
+pub type SqliteDataSource = SqlDataSource<SqliteConnection>;
+
+impl SqlConnection for SqliteConnection {
+    const DATA_SOURCE: &'static str = "SqliteDataSource";
+
+    /// Every connection to `:memory:` sees its own empty database, so an
+    /// in-memory pool is held to one connection that is never recycled.
+    fn prepare(cfg: DataSourceConfig) -> DataSourceConfig {
+        if cfg.get_url().contains(":memory:") {
+            cfg.max_size(1)
+                .min_size(1)
+                .idle_timeout(None::<Duration>)
+                .max_lifetime(None::<Duration>)
+        } else {
+            cfg
+        }
+    }
+
+    fn ping(pool: &SqlitePool) -> BoxFuture<'_, sqlx::Result<()>> {
+        Box::pin(async move { sqlx::query("SELECT 1").execute(pool).await.map(|_| ()) })
+    }
+
+    fn server_version(pool: &SqlitePool) -> BoxFuture<'_, Option<String>> {
+        Box::pin(async move {
+            let mut cursor = sqlx::query(r#"SELECT sqlite_version() v"#).fetch(pool);
+            let row = cursor.next().await.ok()??;
+            row.try_get::<&str, &str>("v").ok().map(str::to_string)
+        })
+    }
+}
+
+/// Builds a new pool on every call, see `sqlite()` for a shared one.
+pub async fn sqlite_data_source() -> SqliteDataSource {
+    try_sqlite_data_source()
+        .await
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub async fn try_sqlite_data_source() -> Result<SqliteDataSource, ConfigError> {
+    SqliteDataSource::from_config(DataSourceConfig::from_env("SQLITE")?).await
+}
+
+/// Reads `{NAME}_SQLITE_URL` and the matching pool sizes.
+pub async fn sqlite_data_source_named(name: &str) -> SqliteDataSource {
+    try_sqlite_data_source_named(name)
+        .await
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub async fn try_sqlite_data_source_named(name: &str) -> Result<SqliteDataSource, ConfigError> {
+    let prefix = Backend::Sqlite.named_env_prefix(name);
+    SqliteDataSource::from_config(DataSourceConfig::from_env(&prefix)?).await
+}
+
+/// The SQLite data source held by `REGISTRY`, its pool is built on first use.
+pub async fn sqlite() -> SqliteDataSource {
+    try_sqlite().await.unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub async fn try_sqlite() -> Result<SqliteDataSource, ConfigError> {
+    REGISTRY.sqlite().await
+}
+
+/// The shared data source named `name`, its pool is built on first use.
+pub async fn sqlite_named(name: &str) -> SqliteDataSource {
+    try_sqlite_named(name)
+        .await
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub async fn try_sqlite_named(name: &str) -> Result<SqliteDataSource, ConfigError> {
+    REGISTRY.sqlite_named(name).await
+}
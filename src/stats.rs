@@ -0,0 +1,69 @@
+//! Pool statistics.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// 连接池统计
+///
+/// `acquired`, `wait_time` and `acquire_failures` count since the pool was
+/// built. SQL pools only count connections taken through the data source's
+/// `acquire`, redis pools count every checkout. `in_use` and `is_exhausted`
+/// are read from the pool itself, so they also see the connections taken
+/// by queries run directly on a SQL pool, e.g. `fetch(&pool)`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Connections currently open, idle or in use.
+    pub size: u32,
+    /// Open connections not in use.
+    pub idle: u32,
+    pub max_size: u32,
+    pub acquired: u64,
+    /// Total time spent waiting for a connection.
+    pub wait_time: Duration,
+    /// Attempts that timed out or failed to connect.
+    pub acquire_failures: u64,
+}
+
+impl PoolStats {
+    /// Connections currently handed out.
+    pub fn in_use(&self) -> u32 {
+        self.size.saturating_sub(self.idle)
+    }
+
+    /// Whether every connection the pool may open is in use, so the next
+    /// caller has to wait.
+    pub fn is_exhausted(&self) -> bool {
+        self.max_size > 0 && self.in_use() >= self.max_size
+    }
+}
+
+/// Counters shared by every clone of a data source.
+#[derive(Debug, Default)]
+pub(crate) struct AcquireMetrics {
+    acquired: AtomicU64,
+    wait_nanos: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl AcquireMetrics {
+    pub(crate) fn record(&self, waited: Duration, succeeded: bool) {
+        let counter = if succeeded {
+            &self.acquired
+        } else {
+            &self.failures
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.wait_nanos
+            .fetch_add(waited.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn stats(&self, size: u32, idle: u32, max_size: u32) -> PoolStats {
+        PoolStats {
+            size,
+            idle,
+            max_size,
+            acquired: self.acquired.load(Ordering::Relaxed),
+            wait_time: Duration::from_nanos(self.wait_nanos.load(Ordering::Relaxed)),
+            acquire_failures: self.failures.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Pools shared by every clone of a data source and swapped in place on reload.
+#[cfg(feature = "with-redis")]
+use std::collections::HashMap;
+#[cfg(feature = "with-redis")]
+use std::sync::Mutex;
+use std::sync::{Arc, RwLock};
+
+/// A value shared by every clone, `replace` changes it for all of them.
+pub(crate) struct Swap<T>(Arc<RwLock<T>>);
+
+impl<T: Clone> Swap<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Swap(Arc::new(RwLock::new(value)))
+    }
+
+    pub(crate) fn get(&self) -> T {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Swaps in `value` for every clone, returning the value replaced.
+    pub(crate) fn replace(&self, value: T) -> T {
+        let mut current = self.0.write().unwrap_or_else(|e| e.into_inner());
+        std::mem::replace(&mut *current, value)
+    }
+}
+
+impl<T> Clone for Swap<T> {
+    fn clone(&self) -> Self {
+        Swap(self.0.clone())
+    }
+}
+
+/// A URL and the pool connected to it.
+#[derive(Clone)]
+pub(crate) struct Pooled<P> {
+    pub(crate) url: String,
+    pub(crate) pool: P,
+}
+
+/// Shared values keyed by env prefix, each built once by its first caller.
+///
+/// The map is only locked to find a prefix's slot, which is locked while its
+/// value is built, so a slow build does not hold up other prefixes. A build
+/// that fails is not kept, the next caller tries again.
+#[cfg(feature = "with-redis")]
+pub(crate) struct Slots<T>(Mutex<HashMap<String, Arc<Slot<T>>>>);
+
+#[cfg(feature = "with-redis")]
+struct Slot<T> {
+    value: RwLock<Option<T>>,
+    init: Mutex<()>,
+}
+
+#[cfg(feature = "with-redis")]
+impl<T: Clone> Slots<T> {
+    pub(crate) fn new() -> Self {
+        Slots(Mutex::new(HashMap::new()))
+    }
+
+    pub(crate) fn get_or_try_init<E>(
+        &self,
+        prefix: &str,
+        build: impl FnOnce() -> Result<T, E>,
+    ) -> Result<T, E> {
+        let slot = self
+            .0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(prefix.to_string())
+            .or_insert_with(|| {
+                Arc::new(Slot {
+                    value: RwLock::new(None),
+                    init: Mutex::new(()),
+                })
+            })
+            .clone();
+        if let Some(value) = slot.get() {
+            return Ok(value);
+        }
+        let _init = slot.init.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(value) = slot.get() {
+            return Ok(value);
+        }
+        let value = build()?;
+        *slot.value.write().unwrap_or_else(|e| e.into_inner()) = Some(value.clone());
+        Ok(value)
+    }
+
+    /// The values built so far, with their prefix.
+    pub(crate) fn built(&self) -> Vec<(String, T)> {
+        let slots: Vec<_> = self
+            .0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(prefix, slot)| (prefix.clone(), slot.clone()))
+            .collect();
+        slots
+            .into_iter()
+            .filter_map(|(prefix, slot)| Some((prefix, slot.get()?)))
+            .collect()
+    }
+}
+
+#[cfg(feature = "with-redis")]
+impl<T: Clone> Slot<T> {
+    fn get(&self) -> Option<T> {
+        self.value.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
@@ -0,0 +1,193 @@
+//! TLS settings of the MySQL and PostgreSQL data sources.
+use crate::env;
+use crate::error::ConfigError;
+use crate::named::Backend;
+
+const PG_MODES: [&str; 6] = [
+    "disable",
+    "allow",
+    "prefer",
+    "require",
+    "verify-ca",
+    "verify-full",
+];
+
+const MYSQL_MODES: [&str; 5] = [
+    "disabled",
+    "preferred",
+    "required",
+    "verify_ca",
+    "verify_identity",
+];
+
+/// TLS 配置
+///
+/// Empty by default, leaving TLS to the URL. The settings are added to the
+/// URL when the pool is built, replacing those the URL already has.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsConfig {
+    ssl_mode: Option<String>,
+    root_cert: Option<String>,
+}
+
+impl TlsConfig {
+    /// `sslmode` of PostgreSQL, e.g. `verify-full`, or `ssl-mode` of MySQL,
+    /// e.g. `VERIFY_IDENTITY`.
+    pub fn ssl_mode(mut self, ssl_mode: impl Into<String>) -> Self {
+        self.ssl_mode = Some(ssl_mode.into());
+        self
+    }
+
+    /// PEM file of the CA the server certificate must be signed by.
+    pub fn root_cert(mut self, path: impl Into<String>) -> Self {
+        self.root_cert = Some(path.into());
+        self
+    }
+
+    pub fn get_ssl_mode(&self) -> Option<&str> {
+        self.ssl_mode.as_deref()
+    }
+
+    pub fn get_root_cert(&self) -> Option<&str> {
+        self.root_cert.as_deref()
+    }
+
+    /// Reads `{prefix}_SSL_MODE` and `{prefix}_SSL_ROOT_CERT`.
+    ///
+    /// sqlx 0.3 cannot present a client certificate, so `{prefix}_SSL_CERT`
+    /// and `{prefix}_SSL_KEY` are refused rather than ignored.
+    pub(crate) fn from_env(prefix: &str) -> Result<Self, ConfigError> {
+        let key = |suffix: &str| format!("{}_{}", prefix, suffix);
+        for suffix in &["SSL_CERT", "SSL_KEY"] {
+            if env::is_set(&key(suffix)) {
+                return Err(ConfigError::InvalidValue {
+                    key: key(suffix),
+                    reason: "client certificates are not supported by sqlx".to_string(),
+                });
+            }
+        }
+        Ok(TlsConfig {
+            ssl_mode: ssl_mode_from_env(prefix)?,
+            root_cert: env::opt_var(&key("SSL_ROOT_CERT"))?,
+        })
+    }
+
+    /// Checks the settings against the backend of `url`, and that the root
+    /// certificate is a readable PEM file. `key(suffix, field)` names a setting in errors.
+    pub(crate) fn check(
+        &self,
+        url: &str,
+        key: impl Fn(&str, &str) -> String,
+    ) -> Result<(), ConfigError> {
+        if *self == TlsConfig::default() {
+            return Ok(());
+        }
+        let scheme = url::Url::parse(url)
+            .map(|url| url.scheme().to_string())
+            .unwrap_or_default();
+        let modes: &[&str] = match Backend::from_scheme(&scheme) {
+            Some(Backend::Pg) => &PG_MODES,
+            Some(Backend::MySql) => &MYSQL_MODES,
+            _ => {
+                return Err(ConfigError::InvalidValue {
+                    key: key("SSL_MODE", "tls"),
+                    reason: format!("TLS settings are not used by {}://", scheme),
+                })
+            }
+        };
+        if let Some(ssl_mode) = &self.ssl_mode {
+            let mode = ssl_mode.to_lowercase();
+            if !modes.contains(&mode.as_str()) {
+                return Err(ConfigError::InvalidValue {
+                    key: key("SSL_MODE", "ssl_mode"),
+                    reason: format!("expected one of {}", modes.join(", ")),
+                });
+            }
+            // Only the verifying modes, the last two, check the CA.
+            if self.root_cert.is_some() && !modes[modes.len() - 2..].contains(&mode.as_str()) {
+                return Err(ConfigError::InvalidValue {
+                    key: key("SSL_MODE", "ssl_mode"),
+                    reason: format!(
+                        "the root certificate is only checked with {} or {}",
+                        modes[modes.len() - 2],
+                        modes[modes.len() - 1]
+                    ),
+                });
+            }
+        }
+        if let Some(path) = &self.root_cert {
+            let pem = std::fs::read_to_string(path).map_err(|e| ConfigError::InvalidFile {
+                path: path.clone(),
+                reason: format!("{}: {}", key("SSL_ROOT_CERT", "root_cert"), e),
+            })?;
+            if !pem.contains("-----BEGIN CERTIFICATE-----") {
+                return Err(ConfigError::InvalidFile {
+                    path: path.clone(),
+                    reason: format!(
+                        "{}: not a PEM certificate",
+                        key("SSL_ROOT_CERT", "root_cert")
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// `url` with these settings as query parameters, the mode in the case
+    /// the backend expects. Without a mode, a root certificate implies
+    /// `verify-ca`, as it does for libpq.
+    #[cfg(any(
+        feature = "with-mysql",
+        feature = "with-postgres",
+        feature = "with-sqlite"
+    ))]
+    pub(crate) fn apply(&self, url: &str) -> String {
+        let mut parsed = match url::Url::parse(url) {
+            Ok(parsed) => parsed,
+            Err(_) => return url.to_string(),
+        };
+        let (mode_key, ca_key, verify_ca, case): (_, _, _, fn(&str) -> String) =
+            match Backend::from_scheme(parsed.scheme()) {
+                Some(Backend::Pg) => ("sslmode", "sslrootcert", "verify-ca", str::to_lowercase),
+                Some(Backend::MySql) => ("ssl-mode", "ssl-ca", "VERIFY_CA", str::to_uppercase),
+                _ => return url.to_string(),
+            };
+        let ssl_mode = self.ssl_mode.as_deref().map(case);
+        let ssl_mode = ssl_mode
+            .as_deref()
+            .or_else(|| self.root_cert.as_ref().map(|_| verify_ca));
+        let settings: Vec<(&str, &str)> =
+            vec![(mode_key, ssl_mode), (ca_key, self.get_root_cert())]
+                .into_iter()
+                .filter_map(|(key, value)| value.map(|value| (key, value)))
+                .collect();
+        if settings.is_empty() {
+            return url.to_string();
+        }
+        let kept: Vec<(String, String)> = parsed
+            .query_pairs()
+            .filter(|(key, _)| settings.iter().all(|(setting, _)| key.as_ref() != *setting))
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect();
+        parsed
+            .query_pairs_mut()
+            .clear()
+            .extend_pairs(kept)
+            .extend_pairs(settings);
+        parsed.to_string()
+    }
+}
+
+/// Reads `{prefix}_SSL_MODE`, or `{prefix}_SSLMODE`, which is deprecated.
+pub(crate) fn ssl_mode_from_env(prefix: &str) -> Result<Option<String>, ConfigError> {
+    let key = format!("{}_SSL_MODE", prefix);
+    if let Some(ssl_mode) = env::opt_var(&key)? {
+        return Ok(Some(ssl_mode));
+    }
+    let deprecated = format!("{}_SSLMODE", prefix);
+    let ssl_mode = env::opt_var(&deprecated)?;
+    if ssl_mode.is_some() {
+        log::warn!("{} is deprecated, set {} instead", deprecated, key);
+    }
+    Ok(ssl_mode)
+}
@@ -0,0 +1,227 @@
+//! Checks the configuration of every backend without connecting.
+use std::collections::HashSet;
+
+use crate::config::default_min_size;
+use crate::env;
+use crate::error::ConfigError;
+use crate::named::{configured_names, Backend};
+use crate::retry::RetryPolicy;
+use crate::tls::TlsConfig;
+
+const BACKENDS: [Backend; 4] = [Backend::MySql, Backend::Pg, Backend::Redis, Backend::Sqlite];
+
+/// 校验配置
+///
+/// Returns every problem found, an empty list means the configuration is usable.
+/// Only backends whose feature is enabled are checked. The unnamed redis
+/// data source, and that of the only SQL backend enabled, must be
+/// configured; others are checked when their URL or host is set.
+pub fn validate() -> Vec<ConfigError> {
+    let mut problems = Vec::new();
+    if let Err(e) = env::load() {
+        problems.push(e);
+    }
+    let mut files = vec![".env".to_string()];
+    files.extend(env::profile().map(|profile| format!(".env.{}", profile)));
+    for file in &files {
+        problems.extend(duplicate_keys(file));
+    }
+    for &backend in BACKENDS.iter().filter(|&&backend| enabled(backend)) {
+        let prefix = backend.env_prefix();
+        if required(backend)
+            || env::is_set(&format!("{}_URL", prefix))
+            || env::is_set(&format!("{}_HOST", prefix))
+            || env::is_set(&format!("{}_SENTINELS", prefix))
+            || env::is_set(&format!("{}_CLUSTER_NODES", prefix))
+        {
+            check(backend, prefix, &mut problems);
+        }
+        for name in configured_names(backend) {
+            check(backend, &backend.named_env_prefix(&name), &mut problems);
+        }
+    }
+    if env::is_set("DATABASE_URL") {
+        check_any("DATABASE", &mut problems);
+    }
+    problems
+}
+
+fn enabled(backend: Backend) -> bool {
+    match backend {
+        Backend::MySql => cfg!(feature = "with-mysql"),
+        Backend::Pg => cfg!(feature = "with-postgres"),
+        Backend::Redis => cfg!(feature = "with-redis"),
+        Backend::Sqlite => cfg!(feature = "with-sqlite"),
+    }
+}
+
+/// `REDIS_POOL` always reads the unnamed redis data source, `data_source()`
+/// that of the SQL backend when only one is enabled.
+fn required(backend: Backend) -> bool {
+    match backend {
+        Backend::Redis => enabled(backend),
+        _ => {
+            let sql_enabled = BACKENDS
+                .iter()
+                .filter(|&&other| other != Backend::Redis && enabled(other))
+                .count();
+            enabled(backend) && sql_enabled == 1 && !env::is_set("DATABASE_URL")
+        }
+    }
+}
+
+/// Checks `DATABASE_URL`, whose backend follows from its scheme.
+fn check_any(prefix: &str, problems: &mut Vec<ConfigError>) {
+    let key = format!("{}_URL", prefix);
+    let scheme = match env::url(&key) {
+        Ok(url) => url::Url::parse(&url)
+            .map(|url| url.scheme().to_string())
+            .unwrap_or_default(),
+        Err(e) => {
+            problems.push(e);
+            return;
+        }
+    };
+    match Backend::from_scheme(&scheme)
+        .filter(|&backend| backend != Backend::Redis && enabled(backend))
+    {
+        Some(backend) => check(backend, prefix, problems),
+        None => problems.push(ConfigError::MalformedUrl {
+            key,
+            reason: format!("no SQL backend is available for {}://", scheme),
+        }),
+    }
+}
+
+pub(crate) fn check(backend: Backend, prefix: &str, problems: &mut Vec<ConfigError>) {
+    let key = |suffix: &str| format!("{}_{}", prefix, suffix);
+    #[cfg(feature = "with-redis")]
+    {
+        if backend == Backend::Redis && env::is_set(&key("CLUSTER_NODES")) {
+            let prefix = crate::redis_cluster::cluster_prefix(prefix);
+            if let Err(e) = crate::redis_cluster::nodes(&prefix) {
+                problems.push(e);
+            }
+            return check_redis_pool(&prefix, problems);
+        }
+        if backend == Backend::Redis && env::is_set(&key("SENTINELS")) {
+            // The master is only known once Sentinel is asked.
+            if let Err(e) = crate::sentinel::Sentinel::from_env(prefix) {
+                problems.push(e);
+            }
+            return check_redis_pool(prefix, problems);
+        }
+    }
+    let mut urls = Vec::new();
+    match env::backend_url(backend, prefix) {
+        Ok(url) => {
+            let scheme = url::Url::parse(&url)
+                .map(|url| url.scheme().to_string())
+                .unwrap_or_default();
+            if !backend.schemes().contains(&scheme.as_str()) {
+                problems.push(ConfigError::MalformedUrl {
+                    key: key("URL"),
+                    reason: format!(
+                        "expected a {}:// URL, found {}://",
+                        backend.schemes()[0],
+                        scheme
+                    ),
+                });
+            }
+            urls.push(url);
+        }
+        Err(e) => problems.push(e),
+    }
+    #[cfg(feature = "with-redis")]
+    {
+        if backend == Backend::Redis {
+            return check_redis_pool(prefix, problems);
+        }
+    }
+    if let Err(e) = RetryPolicy::from_env(prefix) {
+        problems.push(e);
+    }
+    match TlsConfig::from_env(prefix) {
+        Ok(tls) => problems.extend(
+            urls.first()
+                .and_then(|url| tls.check(url, |suffix, _| key(suffix)).err()),
+        ),
+        Err(e) => problems.push(e),
+    }
+    if backend == Backend::MySql || backend == Backend::Pg {
+        check_replicas(backend, prefix, problems);
+    }
+    let max_size = env::pool_size(&key("MAX_POOL_SIZE"), crate::MAX_POOL_SIZE);
+    let min_size = env::pool_size(
+        &key("MIN_POOL_SIZE"),
+        default_min_size(*max_size.as_ref().unwrap_or(&crate::MAX_POOL_SIZE)),
+    );
+    match (max_size, min_size) {
+        (Ok(0), _) => problems.push(ConfigError::InvalidPoolSize {
+            key: key("MAX_POOL_SIZE"),
+            value: "0".to_string(),
+        }),
+        (Ok(max_size), Ok(min_size)) if min_size > max_size => {
+            problems.push(ConfigError::InvalidPoolSize {
+                key: key("MIN_POOL_SIZE"),
+                value: min_size.to_string(),
+            })
+        }
+        (max_size, min_size) => {
+            problems.extend(max_size.err());
+            problems.extend(min_size.err());
+        }
+    }
+}
+
+/// Checks the pool settings of `prefix`.
+#[cfg(feature = "with-redis")]
+fn check_redis_pool(prefix: &str, problems: &mut Vec<ConfigError>) {
+    if let Err(e) = crate::redis_config::RedisPoolConfig::from_env(prefix) {
+        problems.push(e);
+    }
+}
+
+/// Checks `{prefix}_REPLICA_URLS`, which need not be set.
+fn check_replicas(backend: Backend, prefix: &str, problems: &mut Vec<ConfigError>) {
+    let key = format!("{}_REPLICA_URLS", prefix);
+    let urls = match env::opt_var(&key) {
+        Ok(urls) => urls.unwrap_or_default(),
+        Err(e) => return problems.push(e),
+    };
+    for url in urls.split(',').map(str::trim).filter(|url| !url.is_empty()) {
+        let reason = match url::Url::parse(url) {
+            Ok(url) if backend.schemes().contains(&url.scheme()) => continue,
+            Ok(url) => format!(
+                "expected a {}:// URL, found {}://",
+                backend.schemes()[0],
+                url.scheme()
+            ),
+            Err(e) => e.to_string(),
+        };
+        problems.push(ConfigError::MalformedUrl {
+            key: key.clone(),
+            reason,
+        });
+    }
+}
+
+/// Reports keys set more than once in the dotenv file at `path`, if any.
+#[allow(deprecated)]
+pub(crate) fn duplicate_keys(path: &str) -> Vec<ConfigError> {
+    let iter = match dotenv::from_filename_iter(path) {
+        Ok(iter) => iter,
+        Err(_) => return Vec::new(),
+    };
+    let mut seen = HashSet::new();
+    let mut problems = Vec::new();
+    for (key, _) in iter.filter_map(Result::ok) {
+        if !seen.insert(key.clone()) {
+            problems.push(ConfigError::DuplicateKey {
+                key,
+                path: path.to_string(),
+            });
+        }
+    }
+    problems
+}